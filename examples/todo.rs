@@ -1,8 +1,16 @@
 use flux_ui::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 fn main() {
-    App::run(
+    App::on_error(|error| eprintln!("{}", error));
+    App::run_with(
+        AppOptions {
+            id: "flux-todo".into(),
+            ..Default::default()
+        },
         WindowOptions {
+            min_size: Some(Vec2::new(480.0, 360.0)),
             background: WindowBackground::Blurred,
             show_titlebar: false,
             ..Default::default()
@@ -11,12 +19,14 @@ fn main() {
     );
 }
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 struct Todo {
+    id: usize,
     name: String,
     done: bool,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 struct Todos {
     items: Vec<Todo>,
 }
@@ -27,32 +37,52 @@ struct ContentView;
 impl Component for ContentView {
     fn build(&self, ctx: &mut Context) -> impl View {
         let selected = ctx.state(|| Option::<usize>::None);
-        let todos = ctx.state(|| Todos {
+        let todos = ctx.persistent_state("todos", || Todos {
             items: vec![
                 Todo {
+                    id: 0,
                     name: "First".into(),
                     done: false,
                 },
                 Todo {
+                    id: 1,
                     name: "Second".into(),
                     done: false,
                 },
                 Todo {
+                    id: 2,
                     name: "Third".into(),
                     done: false,
                 },
                 Todo {
+                    id: 3,
                     name: "Fourth".into(),
                     done: false,
                 },
             ],
         });
+        ctx.undoable(todos);
+
+        let (titlebar, background) = match ctx.color_scheme() {
+            ColorScheme::Light => (
+                Color::from_rgba(223, 223, 223, 200),
+                Color::from_rgba(255, 255, 255, 200),
+            ),
+            ColorScheme::Dark => (
+                Color::from_rgba(48, 48, 48, 200),
+                Color::from_rgba(24, 24, 24, 200),
+            ),
+        };
 
         col![
-            spacer()
-                .height(28.0)
-                .background::<Color>((223, 223, 223, 200).into()),
-            ListView { selected, todos }.background(Color::from_rgba(255, 255, 255, 200))
+            spacer().height(28.0).background(titlebar),
+            ListView { selected, todos }
+                .error_boundary(|error, retry| {
+                    label(format!("{}, click to retry", error))
+                        .padding_all(16.0)
+                        .on_click(move |ctx| retry.retry(ctx))
+                })
+                .background(background)
         ]
     }
 }
@@ -70,8 +100,9 @@ impl Component for ListView {
 
         let selected = ctx.get(self.selected);
         col![
-            col(ContentBuilder::from_items(
+            col(ContentBuilder::from_keyed_items(
                 ctx.get(self.todos).items.iter().enumerate(),
+                |(_, item)| item.id,
                 |(index, item)| {
                     ListItemView {
                         index,
@@ -135,6 +166,7 @@ impl Component for ListView {
             AddButton {
                 todos: todos_binding
             }
+            .animate_layout(Curve::ease_out(Duration::from_millis(200)))
         ]
         .spacing(16.0)
         .padding_all(16.0)
@@ -151,10 +183,16 @@ struct ListItemView {
 }
 
 impl Component for ListItemView {
-    fn build(&self, _ctx: &mut Context) -> impl View {
+    fn build(&self, ctx: &mut Context) -> impl View {
         let Self { index, todos, .. } = *self;
+        let done = ctx.lens(
+            todos,
+            move |todos| todos.items.get(index).map(|todo| &todo.done),
+            move |todos| todos.items.get_mut(index).map(|todo| &mut todo.done),
+        );
 
         row![
+            Checkbox { checked: done },
             label(&self.todo.name).size(16.0),
             spacer().height(0.0),
             label("Delete")
@@ -172,6 +210,7 @@ impl Component for ListItemView {
                     ctx.get_mut(todos).items.remove(index);
                 })
         ]
+        .spacing(12.0)
         .padding_all(16.0)
         .background(BoxDecoration {
             color: Some(Color::WHITE),
@@ -189,6 +228,38 @@ impl Component for ListItemView {
     }
 }
 
+#[derive(PartialEq)]
+struct Checkbox {
+    checked: Binding<bool>,
+}
+
+impl Component for Checkbox {
+    fn build(&self, ctx: &mut Context) -> impl View {
+        let Self { checked } = *self;
+
+        spacer()
+            .width(16.0)
+            .height(16.0)
+            .background(BoxDecoration {
+                color: ctx
+                    .try_get(checked)
+                    .is_some_and(|&checked| checked)
+                    .then_some(0x404040.into()),
+                border: Some(BorderDecoration {
+                    width: 2.0,
+                    color: 0x404040.into(),
+                }),
+                radius: 4.0,
+                smoothing: 0.6,
+            })
+            .on_click(move |ctx| {
+                if let Some(mut checked) = ctx.try_get_mut(checked) {
+                    *checked = !*checked;
+                }
+            })
+    }
+}
+
 #[derive(PartialEq)]
 struct AddButton {
     todos: Binding<Todos>,
@@ -213,13 +284,14 @@ impl Component for AddButton {
         })
         .on_mouse(move |ctx, prev_state, state| {
             match state {
-                MouseState::Idle => *ctx.get_mut(color) = Color::WHITE,
-                MouseState::Hover => *ctx.get_mut(color) = 0xEFEFEF.into(),
-                MouseState::Pressed => *ctx.get_mut(color) = 0xDFDFDF.into(),
+                MouseState::Idle => ctx.set(color, Color::WHITE),
+                MouseState::Hover => ctx.set(color, 0xEFEFEF.into()),
+                MouseState::Pressed => ctx.set(color, 0xDFDFDF.into()),
             }
             if state == MouseState::Hover && prev_state == MouseState::Pressed {
                 let items = &mut ctx.get_mut(todos).items;
                 items.push(Todo {
+                    id: items.iter().map(|item| item.id + 1).max().unwrap_or(0),
                     name: format!("Item {}", items.len() + 1),
                     done: false,
                 });
@@ -0,0 +1,70 @@
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Curve {
+    pub duration: Duration,
+    pub easing: Easing,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Curve {
+    pub fn linear(duration: Duration) -> Self {
+        Self {
+            duration,
+            easing: Easing::Linear,
+        }
+    }
+
+    pub fn ease_in(duration: Duration) -> Self {
+        Self {
+            duration,
+            easing: Easing::EaseIn,
+        }
+    }
+
+    pub fn ease_out(duration: Duration) -> Self {
+        Self {
+            duration,
+            easing: Easing::EaseOut,
+        }
+    }
+
+    pub fn ease_in_out(duration: Duration) -> Self {
+        Self {
+            duration,
+            easing: Easing::EaseInOut,
+        }
+    }
+
+    pub fn progress(&self, start: Instant, time: Instant) -> f32 {
+        if self.duration.is_zero() {
+            return 1.0;
+        }
+        let t = time.saturating_duration_since(start).as_secs_f32() / self.duration.as_secs_f32();
+        self.easing.apply(t.clamp(0.0, 1.0))
+    }
+}
+
+impl Easing {
+    pub fn apply(&self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+        }
+    }
+}
@@ -1,33 +1,68 @@
 use crate::{
     core::{
-        context::{Context, ContextMut, StateChanges, StateDependencies, States},
-        interaction::Interaction,
+        context::{Binding, Context, ContextMut, StateChanges, StateDependencies, States},
+        effect::run_effects,
+        error::{boundary_key, fail_boundary, report, set_error_hook, ViewError},
+        interaction::{Interaction, Lifecycle},
+        persistence::{default_app_id, default_data_dir, save_persistent_states, Persistence},
+        sender::{AppEvent, EventProxy},
+        store::{register_stores, set_app_thread},
         view::View,
-        view_tree::ViewTree,
+        view_tree::{DumpOptions, ViewTree},
+        window::{ColorScheme, SystemColorScheme, WindowCommand, WindowHandle},
     },
     graphics::renderer::Renderer,
-    utils::{bigraph::Bigraph, id_vec::Id},
+    utils::bigraph::Bigraph,
 };
 use glam::Vec2;
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    rc::Rc,
+    time::Instant,
+};
 use winit::{
     application::ApplicationHandler,
-    dpi::{LogicalSize, PhysicalPosition, Size},
+    dpi::{LogicalPosition, LogicalSize, PhysicalPosition, Position, Size},
     event::{DeviceId, ElementState, MouseButton, WindowEvent},
     event_loop::{ActiveEventLoop, EventLoop},
-    platform::macos::WindowAttributesExtMacOS,
-    window::{Theme, Window, WindowAttributes, WindowId},
+    window::{Fullscreen, Window, WindowAttributes, WindowId, WindowLevel},
 };
 
+pub struct AppOptions {
+    /// Keeps the persistent states of different apps apart, the executable's name by default.
+    pub id: String,
+    /// Where persistent states are saved, a directory named after the id in the user's data
+    /// directory by default.
+    pub data_dir: Option<PathBuf>,
+}
+
+impl Default for AppOptions {
+    fn default() -> Self {
+        Self {
+            id: default_app_id(),
+            data_dir: None,
+        }
+    }
+}
+
 pub struct WindowOptions {
     pub title: String,
     pub size: Vec2,
+    pub min_size: Option<Vec2>,
+    pub max_size: Option<Vec2>,
+    pub position: Option<Vec2>,
+    pub resizable: bool,
+    pub color_scheme: Option<ColorScheme>,
     pub background: WindowBackground,
     pub show_title: bool,
     pub show_buttons: bool,
     pub show_titlebar: bool,
 }
 
+/// Only macOS shows through `Transparent` and `Blurred` windows. The software renderer used
+/// elsewhere can't present transparent pixels, so windows there are always opaque and filled
+/// with the background of the color scheme.
 pub enum WindowBackground {
     Opaque,
     Transparent,
@@ -39,6 +74,11 @@ impl Default for WindowOptions {
         Self {
             title: "Flux".into(),
             size: Vec2::new(800.0, 600.0),
+            min_size: None,
+            max_size: None,
+            position: None,
+            resizable: true,
+            color_scheme: None,
             background: WindowBackground::Opaque,
             show_title: true,
             show_buttons: true,
@@ -47,6 +87,62 @@ impl Default for WindowOptions {
     }
 }
 
+impl WindowOptions {
+    fn window_attributes(&self) -> WindowAttributes {
+        let mut window_attributes = WindowAttributes::default()
+            .with_theme(self.color_scheme.map(Into::into))
+            .with_title(self.title.clone())
+            .with_inner_size(logical_size(self.size))
+            .with_resizable(self.resizable);
+
+        if let Some(min_size) = self.min_size {
+            window_attributes = window_attributes.with_min_inner_size(logical_size(min_size));
+        }
+        if let Some(max_size) = self.max_size {
+            window_attributes = window_attributes.with_max_inner_size(logical_size(max_size));
+        }
+        if let Some(position) = self.position {
+            window_attributes = window_attributes.with_position(logical_position(position));
+        }
+
+        self.platform_window_attributes(window_attributes)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn platform_window_attributes(&self, window_attributes: WindowAttributes) -> WindowAttributes {
+        use winit::platform::macos::WindowAttributesExtMacOS;
+
+        window_attributes
+            .with_blur(matches!(self.background, WindowBackground::Blurred))
+            .with_transparent(!matches!(self.background, WindowBackground::Opaque))
+            .with_title_hidden(!self.show_title)
+            .with_titlebar_buttons_hidden(!self.show_buttons)
+            .with_titlebar_transparent(!self.show_titlebar)
+            .with_fullsize_content_view(!self.show_titlebar)
+    }
+
+    // Other platforms draw the titlebar as part of the system decorations, which can't be made
+    // transparent. Hiding the titlebar hides the decorations, a hidden title leaves it empty and
+    // hidden buttons are disabled, which is as close as the window managers allow.
+    #[cfg(not(target_os = "macos"))]
+    fn platform_window_attributes(&self, window_attributes: WindowAttributes) -> WindowAttributes {
+        use winit::window::WindowButtons;
+
+        let window_attributes = if self.show_title {
+            window_attributes
+        } else {
+            window_attributes.with_title("")
+        };
+        window_attributes
+            .with_decorations(self.show_titlebar)
+            .with_enabled_buttons(if self.show_buttons {
+                WindowButtons::all()
+            } else {
+                WindowButtons::empty()
+            })
+    }
+}
+
 pub struct App {
     window_options: WindowOptions,
     state: AppState,
@@ -55,11 +151,13 @@ pub struct App {
     states: States,
     state_dependencies: StateDependencies,
     state_changes: StateChanges,
+    window_handle: WindowHandle,
+    needs_layout: bool,
 }
 
 enum AppState {
     Uninitialized,
-    Ok(Window, Renderer),
+    Ok(Rc<Window>, Renderer),
 }
 
 struct Cursor {
@@ -69,11 +167,35 @@ struct Cursor {
 
 impl App {
     pub fn run(window_options: WindowOptions, root: impl View) {
-        let mut states = HashMap::new();
+        Self::run_with(AppOptions::default(), window_options, root);
+    }
+
+    pub fn run_with(app_options: AppOptions, window_options: WindowOptions, root: impl View) {
+        set_app_thread();
+        let event_loop = EventLoop::with_user_event().build().unwrap();
+
+        let mut states: States = HashMap::new();
+        states.insert(
+            EventProxy::key(),
+            Box::new(EventProxy(event_loop.create_proxy())),
+        );
+        states.insert(
+            Binding::<SystemColorScheme>::global().into(),
+            Box::new(SystemColorScheme(
+                window_options.color_scheme.unwrap_or(ColorScheme::Light),
+            )),
+        );
+        let data_dir = app_options
+            .data_dir
+            .unwrap_or_else(|| default_data_dir(&app_options.id));
+        states.insert(Persistence::key(), Box::new(Persistence::load(&data_dir)));
+        register_stores(&mut states);
+
         let mut state_dependencies = Bigraph::new();
+        let mut state_changes = HashSet::new();
         let tree = ViewTree::build_from(
-            &mut Context::new(&mut states, &mut state_dependencies),
-            Vec2::new(800.0, 600.0),
+            &mut Context::new(&mut states, &mut state_dependencies, &mut state_changes),
+            window_options.size,
             root,
         );
 
@@ -84,49 +206,155 @@ impl App {
             tree,
             states,
             state_dependencies,
-            state_changes: HashSet::new(),
+            state_changes,
+            window_handle: WindowHandle::new(),
+            needs_layout: true,
         };
 
-        let event_loop = EventLoop::new().unwrap();
         event_loop.run_app(&mut app).unwrap();
     }
+
+    /// Builds and lays out `root` without opening a window and returns the dump of its view tree,
+    /// which lets tests check the layout of a view.
+    pub fn dump_tree(root: impl View, size: Vec2, options: DumpOptions) -> String {
+        let mut states: States = HashMap::new();
+        states.insert(
+            Binding::<SystemColorScheme>::global().into(),
+            Box::new(SystemColorScheme(ColorScheme::Light)),
+        );
+        register_stores(&mut states);
+
+        let mut state_dependencies = Bigraph::new();
+        let mut state_changes = HashSet::new();
+        let tree = ViewTree::build_from(
+            &mut Context::new(&mut states, &mut state_dependencies, &mut state_changes),
+            size,
+            root,
+        );
+        tree.dump(options, &states, &state_dependencies)
+    }
+
+    /// Called with every error caught by an error boundary.
+    pub fn on_error(hook: impl Fn(&ViewError) + 'static) {
+        set_error_hook(Rc::new(hook));
+    }
+
+    fn interact(&mut self, interaction: Interaction) -> bool {
+        let AppState::Ok(window, _) = &self.state else {
+            return false;
+        };
+
+        let mut context = ContextMut::new(
+            &mut self.states,
+            &mut self.state_changes,
+            &mut self.window_handle,
+        );
+        let consumed = self.tree.interact(&mut context, interaction);
+        context.commit_transaction();
+
+        apply_window_commands(window, &mut self.window_handle);
+
+        if !self.state_changes.is_empty() {
+            window.request_redraw();
+        }
+
+        consumed
+    }
+
+    fn request_close(&mut self, event_loop: &ActiveEventLoop) {
+        let vetoed = self.interact(Interaction::Lifecycle(Lifecycle::CloseRequested));
+        // closing from a close request listener doesn't request it again
+        self.window_handle.take_close();
+        if !vetoed {
+            event_loop.exit();
+        }
+    }
+
+    fn apply_close(&mut self, event_loop: &ActiveEventLoop) {
+        if self.window_handle.take_close() {
+            self.request_close(event_loop);
+        }
+    }
+
+    fn set_color_scheme(&mut self, color_scheme: ColorScheme) {
+        let AppState::Ok(window, renderer) = &mut self.state else {
+            return;
+        };
+        renderer.set_color_scheme(color_scheme);
+
+        let mut context = ContextMut::new(
+            &mut self.states,
+            &mut self.state_changes,
+            &mut self.window_handle,
+        );
+        let binding = Binding::<SystemColorScheme>::global();
+        if context.get(binding).0 != color_scheme {
+            context.get_mut(binding).0 = color_scheme;
+            window.request_redraw();
+        }
+    }
 }
 
-impl ApplicationHandler for App {
+impl ApplicationHandler<AppEvent> for App {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         if let AppState::Uninitialized = self.state {
-            let window_attributes = WindowAttributes::default()
-                .with_theme(Some(Theme::Light))
-                .with_title(self.window_options.title.clone())
-                .with_inner_size(Size::new(LogicalSize {
-                    width: self.window_options.size.x,
-                    height: self.window_options.size.y,
-                }))
-                .with_blur(
-                    if let WindowBackground::Blurred = self.window_options.background {
-                        true
-                    } else {
-                        false
-                    },
-                )
-                .with_transparent(
-                    if let WindowBackground::Opaque = self.window_options.background {
-                        false
-                    } else {
-                        true
-                    },
-                )
-                .with_title_hidden(!self.window_options.show_title)
-                .with_titlebar_buttons_hidden(!self.window_options.show_buttons)
-                .with_titlebar_transparent(!self.window_options.show_titlebar)
-                .with_fullsize_content_view(!self.window_options.show_titlebar);
-
-            let window = event_loop.create_window(window_attributes).unwrap();
+            let window_attributes = self.window_options.window_attributes();
+            let window = Rc::new(event_loop.create_window(window_attributes).unwrap());
             let renderer = Renderer::new(&window);
 
+            // the renderer starts out light, so a chosen scheme is passed on even when the
+            // platform doesn't report a theme
+            let color_scheme = window
+                .theme()
+                .map(Into::into)
+                .or(self.window_options.color_scheme);
+
             window.request_redraw();
             self.state = AppState::Ok(window, renderer);
-        };
+
+            if let Some(color_scheme) = color_scheme {
+                self.set_color_scheme(color_scheme);
+            }
+        }
+        self.interact(Interaction::Lifecycle(Lifecycle::Resumed));
+        self.apply_close(event_loop);
+    }
+
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: AppEvent) {
+        match event {
+            AppEvent::Update(update) => {
+                let mut context = ContextMut::new(
+                    &mut self.states,
+                    &mut self.state_changes,
+                    &mut self.window_handle,
+                );
+                update(&mut context);
+                context.commit_transaction();
+            }
+        }
+
+        if let AppState::Ok(window, _) = &self.state {
+            apply_window_commands(window, &mut self.window_handle);
+            if !self.state_changes.is_empty() {
+                window.request_redraw();
+            }
+        }
+        self.apply_close(event_loop);
+    }
+
+    fn suspended(&mut self, event_loop: &ActiveEventLoop) {
+        self.interact(Interaction::Lifecycle(Lifecycle::Suspended));
+        self.apply_close(event_loop);
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.interact(Interaction::Lifecycle(Lifecycle::Exiting));
+
+        let changed = self.state_changes.iter().copied().collect::<Vec<_>>();
+        Persistence::with(&mut self.states, |persistence, _| {
+            persistence.mark_changed(&changed)
+        });
+        save_persistent_states(&mut self.states);
     }
 
     fn window_event(
@@ -134,6 +362,18 @@ impl ApplicationHandler for App {
         event_loop: &ActiveEventLoop,
         window_id: WindowId,
         event: WindowEvent,
+    ) {
+        self.handle_window_event(event_loop, window_id, event);
+        self.apply_close(event_loop);
+    }
+}
+
+impl App {
+    fn handle_window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
     ) {
         let AppState::Ok(window, renderer) = &mut self.state else {
             return;
@@ -143,27 +383,79 @@ impl ApplicationHandler for App {
         }
 
         match event {
-            WindowEvent::CloseRequested => {
-                event_loop.exit();
+            WindowEvent::CloseRequested => self.request_close(event_loop),
+            WindowEvent::Focused(focused) => {
+                self.interact(Interaction::Lifecycle(Lifecycle::Focused(focused)));
+            }
+            WindowEvent::Occluded(occluded) => {
+                self.interact(Interaction::Lifecycle(Lifecycle::Occluded(occluded)));
+            }
+            WindowEvent::ThemeChanged(theme) => {
+                self.set_color_scheme(theme.into());
             }
             WindowEvent::Resized(size) => {
                 renderer.set_size(size.width, size.height);
 
-                let size = size.to_logical(window.scale_factor());
-                self.tree.resize(Vec2::new(size.width, size.height));
-
+                self.needs_layout = true;
                 window.request_redraw();
             }
             WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
                 renderer.set_scale_factor(scale_factor);
 
-                let size = window.inner_size().to_logical(scale_factor);
-                self.tree.resize(Vec2::new(size.width, size.height));
-
+                self.needs_layout = true;
                 window.request_redraw();
             }
             WindowEvent::RedrawRequested => {
+                let rebuilt = update(
+                    &mut self.tree,
+                    &mut self.states,
+                    &mut self.state_dependencies,
+                    &mut self.state_changes,
+                );
+
+                if self.needs_layout {
+                    let size = window.inner_size().to_logical(window.scale_factor());
+                    self.tree.resize(Vec2::new(size.width, size.height));
+                    self.needs_layout = false;
+                } else if rebuilt {
+                    self.tree.relayout();
+                }
+
+                if run_effects(
+                    &mut self.states,
+                    &mut self.state_changes,
+                    &mut self.window_handle,
+                ) {
+                    apply_window_commands(window, &mut self.window_handle);
+                    if !self.state_changes.is_empty() {
+                        window.request_redraw();
+                    }
+                }
+
+                if self.tree.tick(Instant::now()) {
+                    window.request_redraw();
+                }
                 renderer.render(|painter| self.tree.draw(painter));
+
+                if let Some(request) = self.window_handle.take_dump() {
+                    let dump =
+                        self.tree
+                            .dump(request.options, &self.states, &self.state_dependencies);
+                    if request.print {
+                        print!("{}", dump);
+                    }
+                    self.window_handle.set_tree_dump(dump);
+                }
+
+                let failures = self.tree.take_failures();
+                if !failures.is_empty() {
+                    for (id, error) in failures {
+                        report(&error);
+                        fail_boundary(&mut self.states, id, error);
+                        self.state_changes.insert(boundary_key(Some(id)));
+                    }
+                    window.request_redraw();
+                }
             }
             WindowEvent::CursorEntered { device_id } => {
                 if self.cursor.is_none() {
@@ -196,18 +488,7 @@ impl ApplicationHandler for App {
                 let position = position.to_logical(window.scale_factor());
                 let position = Vec2::new(position.x, position.y);
 
-                self.tree.interact(
-                    &mut ContextMut::new(&mut self.states, &mut self.state_changes),
-                    Interaction::MouseMove(position),
-                );
-
-                update(
-                    &window,
-                    &mut self.tree,
-                    &mut self.states,
-                    &mut self.state_dependencies,
-                    &mut self.state_changes,
-                );
+                self.interact(Interaction::MouseMove(position));
             }
             WindowEvent::MouseInput {
                 device_id,
@@ -229,52 +510,96 @@ impl ApplicationHandler for App {
                     return;
                 }
 
-                self.tree.interact(
-                    &mut ContextMut::new(&mut self.states, &mut self.state_changes),
-                    match state {
-                        ElementState::Pressed => Interaction::MouseDown(position),
-                        ElementState::Released => Interaction::MouseUp(position),
-                    },
-                );
-
-                update(
-                    &window,
-                    &mut self.tree,
-                    &mut self.states,
-                    &mut self.state_dependencies,
-                    &mut self.state_changes,
-                );
+                self.interact(match state {
+                    ElementState::Pressed => Interaction::MouseDown(position),
+                    ElementState::Released => Interaction::MouseUp(position),
+                });
             }
             _ => {}
         }
     }
 }
 
+// views writing the states they read during build would otherwise rebuild forever
+const MAX_UPDATE_PASSES: usize = 100;
+
 fn update(
-    window: &Window,
     tree: &mut ViewTree,
     states: &mut States,
     state_dependencies: &mut StateDependencies,
     state_changes: &mut StateChanges,
-) {
-    let mut dirty_views = HashSet::<Id>::new();
-    for state_key in state_changes.iter() {
-        dirty_views.extend(
-            state_dependencies
-                .get_v_connections(*state_key)
-                .iter()
-                .filter_map(|id| id.as_ref()),
-        );
-    }
-    state_changes.clear();
+) -> bool {
+    register_stores(states);
 
-    if !dirty_views.is_empty() {
-        let mut context = Context::new(states, state_dependencies);
-        let size = window.inner_size().to_logical(window.scale_factor());
-        for id in dirty_views {
-            tree.rebuild(&mut context, Vec2::new(size.width, size.height), id);
+    let mut rebuilt = false;
+    let mut passes = 0;
+    while !state_changes.is_empty() {
+        if passes == MAX_UPDATE_PASSES {
+            report(&ViewError::failed(format!(
+                "States kept changing after {MAX_UPDATE_PASSES} update passes"
+            )));
+            state_changes.clear();
+            break;
         }
+        passes += 1;
+
+        let changed = state_changes.drain().collect::<Vec<_>>();
+        Persistence::with(states, |persistence, _| persistence.mark_changed(&changed));
+        let mut context = Context::new(states, state_dependencies, state_changes);
+        let dirty_views = context.invalidate(changed);
+        rebuilt |= !dirty_views.is_empty();
+        tree.rebuild_dirty(&mut context, dirty_views);
+    }
+    save_persistent_states(states);
+    rebuilt
+}
 
+fn apply_window_commands(window: &Window, handle: &mut WindowHandle) {
+    if handle.dump_requested() {
         window.request_redraw();
     }
+    for command in handle.take_commands() {
+        match command {
+            WindowCommand::Title(title) => window.set_title(&title),
+            WindowCommand::Size(size) => {
+                let _ = window.request_inner_size(logical_size(size));
+            }
+            WindowCommand::MinSize(min_size) => {
+                window.set_min_inner_size(min_size.map(logical_size));
+            }
+            WindowCommand::MaxSize(max_size) => {
+                window.set_max_inner_size(max_size.map(logical_size));
+            }
+            WindowCommand::Position(position) => {
+                window.set_outer_position(logical_position(position));
+            }
+            WindowCommand::Resizable(resizable) => window.set_resizable(resizable),
+            WindowCommand::Fullscreen(fullscreen) => {
+                window.set_fullscreen(fullscreen.then_some(Fullscreen::Borderless(None)));
+            }
+            WindowCommand::Maximized(maximized) => window.set_maximized(maximized),
+            WindowCommand::Minimized(minimized) => window.set_minimized(minimized),
+            WindowCommand::AlwaysOnTop(always_on_top) => {
+                window.set_window_level(if always_on_top {
+                    WindowLevel::AlwaysOnTop
+                } else {
+                    WindowLevel::Normal
+                });
+            }
+        }
+    }
+}
+
+fn logical_size(size: Vec2) -> Size {
+    Size::new(LogicalSize {
+        width: size.x,
+        height: size.y,
+    })
+}
+
+fn logical_position(position: Vec2) -> Position {
+    Position::new(LogicalPosition {
+        x: position.x,
+        y: position.y,
+    })
 }
@@ -1,48 +1,309 @@
-use crate::utils::{bigraph::Bigraph, id_vec::Id};
+use super::{
+    effect::{Appeared, Disappear, EffectDependencies, PendingEffects},
+    error::{boundary_key, fail_boundary, raise, BoundaryError, ViewError},
+    history::History,
+    lens::{follow_mut, lens_path, resolve, stale_lenses, Lens, LensGet, LensGetMut},
+    persistence::Persistence,
+    sender::Sender,
+    store::{dispatch_through, register_stores, Dispatcher, Subscribers},
+    window::{ColorScheme, SystemColorScheme, WindowHandle},
+};
+use crate::utils::{
+    bigraph::Bigraph,
+    id_vec::Id,
+    tracked_ref::{RefMutTracker, RefTracker, TrackedRefMut},
+};
+use serde::{de::DeserializeOwned, Serialize};
 use std::{
-    any::{Any, TypeId},
+    any::{type_name, Any, TypeId},
     cell::RefCell,
-    collections::{HashMap, HashSet},
+    collections::{hash_map::Entry, HashMap, HashSet},
     marker::PhantomData,
+    panic::Location,
+    rc::Rc,
 };
 
-pub(crate) type StateKey = (Option<Id>, TypeId);
+pub(crate) type StateKey = (Option<Id>, TypeId, StateSlot);
 pub(crate) type States = HashMap<StateKey, Box<dyn Any>>;
-pub(crate) type StateDependencies = Bigraph<Option<Id>, StateKey>;
+pub(crate) type StateDependencies = Bigraph<Dependent, StateKey>;
 pub(crate) type StateChanges = HashSet<StateKey>;
 
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub(crate) enum StateSlot {
+    Index(usize),
+    Key(&'static str),
+    Global(usize),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub(crate) enum Dependent {
+    View(Option<Id>),
+    Derived(StateKey),
+}
+
 pub struct Context<'a> {
     id: Option<Id>,
+    reader: Dependent,
+    slot: usize,
+    ancestors: &'a [Id],
     states: &'a mut States,
     dependencies: RefCell<&'a mut StateDependencies>,
+    changes: &'a mut StateChanges,
+}
+
+struct EnvironmentValue<T>(T);
+
+type Recompute = Rc<dyn Fn(&mut Context) -> bool>;
+
+// recomputing happens outside of the owner's build, so it keeps what the owner's context had
+struct Derivation {
+    dependencies: Box<dyn Any>,
+    ancestors: Vec<Id>,
+    recompute: Recompute,
+}
+
+// The type and call site of every state of a view, `complete` once a build finished
+#[cfg(debug_assertions)]
+struct SlotRecord {
+    slots: Vec<(TypeId, &'static Location<'static>)>,
+    complete: bool,
 }
 
 impl Context<'_> {
     pub(crate) fn new<'a>(
         states: &'a mut States,
         dependencies: &'a mut StateDependencies,
+        changes: &'a mut StateChanges,
     ) -> Context<'a> {
         Context {
             id: None,
+            reader: Dependent::View(None),
+            slot: 0,
+            ancestors: &[],
             states,
             dependencies: RefCell::new(dependencies),
+            changes,
         }
     }
 
-    pub(super) fn with_id<T>(&mut self, id: Id, f: impl FnOnce(&mut Context) -> T) -> T {
-        self.dependencies.get_mut().remove_u(Some(id));
-        f(&mut Context {
+    pub(super) fn with_id<T>(
+        &mut self,
+        id: Id,
+        ancestors: &[Id],
+        f: impl FnOnce(&mut Context) -> T,
+    ) -> T {
+        self.dependencies
+            .get_mut()
+            .remove_u(Dependent::View(Some(id)));
+        let mut context = Context {
             id: Some(id),
+            reader: Dependent::View(Some(id)),
+            slot: 0,
+            ancestors,
+            states: self.states,
+            dependencies: RefCell::new(self.dependencies.get_mut()),
+            changes: self.changes,
+        };
+        let result = f(&mut context);
+
+        #[cfg(debug_assertions)]
+        context.check_slot_count();
+
+        register_stores(self.states);
+        result
+    }
+
+    pub(super) fn release(&mut self, owners: &HashSet<Id>) {
+        let mut released = self
+            .states
+            .keys()
+            .filter(|(owner, _, _)| owner.is_some_and(|owner| owners.contains(&owner)))
+            .copied()
+            .collect::<Vec<_>>();
+        released.extend(stale_lenses(self.states, owners));
+
+        if let Some(history) = self.states.get_mut(&History::key()) {
+            history
+                .downcast_mut::<History>()
+                .unwrap()
+                .release(&released);
+        }
+        if let Some(persistence) = self.states.get_mut(&Persistence::key()) {
+            persistence
+                .downcast_mut::<Persistence>()
+                .unwrap()
+                .release(&released);
+        }
+
+        let dependencies = self.dependencies.get_mut();
+        for &owner in owners {
+            dependencies.remove_u(Dependent::View(Some(owner)));
+        }
+        for key in released {
+            let (owner, type_id, _) = key;
+            if type_id == TypeId::of::<Disappear>() {
+                if let Some(Ok(disappear)) = self.states.remove(&key).map(|state| state.downcast())
+                {
+                    let Disappear(effect) = *disappear;
+                    PendingEffects::push(self.states, owner, effect);
+                }
+            }
+            dependencies.remove_u(Dependent::Derived(key));
+            dependencies.remove_v(key);
+            self.changes.remove(&key);
+            self.states.remove(&key);
+        }
+    }
+
+    pub(crate) fn invalidate(&mut self, mut changed: Vec<StateKey>) -> HashSet<Id> {
+        let mut dirty_views = HashSet::new();
+        while let Some(key) = changed.pop() {
+            self.notify(key);
+
+            let dependents = self
+                .dependencies
+                .get_mut()
+                .get_v_connections(key)
+                .iter()
+                .copied()
+                .collect::<Vec<_>>();
+
+            for dependent in dependents {
+                match dependent {
+                    Dependent::View(Some(id)) => {
+                        dirty_views.insert(id);
+                    }
+                    Dependent::View(None) => {}
+                    Dependent::Derived(key) => {
+                        if self.recompute(key) {
+                            changed.push(key);
+                        }
+                    }
+                }
+            }
+        }
+        dirty_views
+    }
+
+    fn notify(&self, key: StateKey) {
+        let (owner, _, slot) = key;
+        let Some(Subscribers(subscribers)) = self
+            .states
+            .get(&(owner, TypeId::of::<Subscribers>(), slot))
+            .and_then(|state| state.downcast_ref())
+        else {
+            return;
+        };
+        if let Some(value) = self.states.get(&key) {
+            for subscriber in subscribers {
+                subscriber(value.as_ref());
+            }
+        }
+    }
+
+    fn recompute(&mut self, key: StateKey) -> bool {
+        let (owner, _, slot) = key;
+        let Some(derivation) = self
+            .states
+            .get(&(owner, TypeId::of::<Derivation>(), slot))
+            .and_then(|state| state.downcast_ref::<Derivation>())
+        else {
+            return false;
+        };
+        let ancestors = derivation.ancestors.clone();
+        let recompute = derivation.recompute.clone();
+        recompute(&mut Context {
+            id: owner,
+            reader: Dependent::Derived(key),
+            slot: 0,
+            ancestors: &ancestors,
+            states: self.states,
+            dependencies: RefCell::new(self.dependencies.get_mut()),
+            changes: self.changes,
+        })
+    }
+
+    fn derive<T>(&mut self, key: StateKey, compute: impl FnOnce(&Context) -> T) -> T {
+        self.dependencies
+            .get_mut()
+            .remove_u(Dependent::Derived(key));
+        compute(&Context {
+            id: self.id,
+            reader: Dependent::Derived(key),
+            slot: 0,
+            ancestors: self.ancestors,
             states: self.states,
             dependencies: RefCell::new(self.dependencies.get_mut()),
+            changes: self.changes,
         })
     }
+
+    pub(crate) fn id(&self) -> Option<Id> {
+        self.id
+    }
+
+    pub(crate) fn boundary_error(&self) -> Option<ViewError> {
+        let key = boundary_key(self.id);
+        self.dependencies
+            .borrow_mut()
+            .add_connection(self.reader, key);
+        self.states
+            .get(&key)
+            .and_then(|state| state.downcast_ref::<BoundaryError>())
+            .map(|BoundaryError(error)| error.clone())
+    }
+
+    pub(crate) fn fail_boundary(&mut self, id: Id, error: ViewError) {
+        fail_boundary(self.states, id, error);
+    }
+
+    pub(crate) fn provide<T: Any + PartialEq>(&mut self, value: T) {
+        let key = (
+            self.id,
+            TypeId::of::<EnvironmentValue<T>>(),
+            StateSlot::Index(0),
+        );
+        match self
+            .states
+            .get_mut(&key)
+            .and_then(|state| state.downcast_mut::<EnvironmentValue<T>>())
+        {
+            Some(EnvironmentValue(previous)) if *previous == value => {}
+            Some(EnvironmentValue(previous)) => {
+                *previous = value;
+                self.changes.insert(key);
+            }
+            None => {
+                self.states.insert(key, Box::new(EnvironmentValue(value)));
+            }
+        }
+    }
 }
 
 impl Context<'_> {
+    #[track_caller]
     pub fn state<T: Any>(&mut self, init: impl FnOnce() -> T) -> Binding<T> {
+        let index = self.slot;
+        self.slot += 1;
+
+        #[cfg(debug_assertions)]
+        self.check_slot_type::<T>(index);
+
+        self.state_in(StateSlot::Index(index), init)
+    }
+
+    pub fn state_keyed<T: Any>(
+        &mut self,
+        key: &'static str,
+        init: impl FnOnce() -> T,
+    ) -> Binding<T> {
+        self.state_in(StateSlot::Key(key), init)
+    }
+
+    fn state_in<T: Any>(&mut self, slot: StateSlot, init: impl FnOnce() -> T) -> Binding<T> {
         let binding = Binding {
             owner: self.id,
+            slot,
             _phantom_data: PhantomData,
         };
         self.states
@@ -51,17 +312,345 @@ impl Context<'_> {
         binding
     }
 
+    pub fn persistent_state<T: Any + Serialize + DeserializeOwned>(
+        &mut self,
+        key: &'static str,
+        init: impl FnOnce() -> T,
+    ) -> Binding<T> {
+        let binding = Binding::new(None, StateSlot::Key(key));
+        if !self.states.contains_key(&binding.into()) {
+            let value = Persistence::with(self.states, |persistence, _| {
+                persistence.restore(binding.into(), key)
+            })
+            .unwrap_or_else(init);
+            self.states.insert(binding.into(), Box::new(value));
+        }
+        binding
+    }
+
+    #[track_caller]
+    pub fn derived<T: Any + PartialEq>(
+        &mut self,
+        compute: impl Fn(&Context) -> T + 'static,
+    ) -> Binding<T> {
+        self.derived_with((), compute)
+    }
+
+    /// Like `derived`, but also computes again when `dependencies` differ from the last build,
+    /// for values that depend on props the closure captures.
+    #[track_caller]
+    pub fn derived_with<D: Any + PartialEq, T: Any + PartialEq>(
+        &mut self,
+        dependencies: D,
+        compute: impl Fn(&Context) -> T + 'static,
+    ) -> Binding<T> {
+        let index = self.slot;
+        self.slot += 1;
+
+        #[cfg(debug_assertions)]
+        self.check_slot_type::<T>(index);
+
+        let binding = Binding {
+            owner: self.id,
+            slot: StateSlot::Index(index),
+            _phantom_data: PhantomData,
+        };
+        let key = binding.into();
+        let derivation_key = (self.id, TypeId::of::<Derivation>(), StateSlot::Index(index));
+
+        let stale = !self.states.contains_key(&key)
+            || self
+                .states
+                .get(&derivation_key)
+                .and_then(|state| state.downcast_ref::<Derivation>())
+                .is_none_or(|derivation| {
+                    derivation.dependencies.downcast_ref() != Some(&dependencies)
+                });
+
+        // the closure of the latest build is kept, so recomputing sees its current props
+        let recompute: Recompute = Rc::new(move |context: &mut Context| {
+            let value = context.derive(key, |context| compute(context));
+            match context
+                .states
+                .get_mut(&key)
+                .and_then(|state| state.downcast_mut::<T>())
+            {
+                Some(previous) if *previous == value => false,
+                Some(previous) => {
+                    *previous = value;
+                    true
+                }
+                None => {
+                    context.states.insert(key, Box::new(value));
+                    false
+                }
+            }
+        });
+        if stale && recompute(self) {
+            self.changes.insert(key);
+        }
+        self.states.insert(
+            derivation_key,
+            Box::new(Derivation {
+                dependencies: Box::new(dependencies),
+                ancestors: self.ancestors.to_vec(),
+                recompute,
+            }),
+        );
+        binding
+    }
+
+    /// The getters return `None` once the projected value is gone, like an index past the end,
+    /// which makes the lens a missing state instead of panicking.
+    #[track_caller]
+    pub fn lens<P: Any, T: Any>(
+        &mut self,
+        binding: Binding<P>,
+        get: impl Fn(&P) -> Option<&T> + 'static,
+        get_mut: impl Fn(&mut P) -> Option<&mut T> + 'static,
+    ) -> Binding<T> {
+        let key = self.hook::<Lens>();
+        let get: LensGet = Rc::new(move |parent| {
+            let parent = parent.downcast_ref()?;
+            Some(get(parent)?)
+        });
+        let get_mut: LensGetMut = Rc::new(move |parent| {
+            let parent = parent.downcast_mut()?;
+            Some(get_mut(parent)?)
+        });
+        self.states.insert(
+            key,
+            Box::new(Lens {
+                parent: binding.into(),
+                get,
+                get_mut,
+            }),
+        );
+
+        let (owner, _, slot) = key;
+        Binding::new(owner, slot)
+    }
+
+    #[track_caller]
+    pub fn on_appear(&mut self, f: impl FnOnce(&mut ContextMut) + 'static) {
+        let key = self.hook::<Appeared>();
+        if let Entry::Vacant(entry) = self.states.entry(key) {
+            entry.insert(Box::new(Appeared));
+            PendingEffects::push(self.states, self.id, Box::new(f));
+        }
+    }
+
+    #[track_caller]
+    pub fn on_disappear(&mut self, f: impl FnOnce(&mut ContextMut) + 'static) {
+        let key = self.hook::<Disappear>();
+        self.states.insert(key, Box::new(Disappear(Box::new(f))));
+    }
+
+    #[track_caller]
+    pub fn effect<D: Any + PartialEq>(
+        &mut self,
+        dependencies: D,
+        f: impl FnOnce(&mut ContextMut) + 'static,
+    ) {
+        let key = self.hook::<EffectDependencies<D>>();
+        match self
+            .states
+            .get(&key)
+            .and_then(|state| state.downcast_ref::<EffectDependencies<D>>())
+        {
+            Some(EffectDependencies(previous)) if *previous == dependencies => {}
+            _ => {
+                self.states
+                    .insert(key, Box::new(EffectDependencies(dependencies)));
+                PendingEffects::push(self.states, self.id, Box::new(f));
+            }
+        }
+    }
+
+    #[track_caller]
+    fn hook<T: Any>(&mut self) -> StateKey {
+        let index = self.slot;
+        self.slot += 1;
+
+        #[cfg(debug_assertions)]
+        self.check_slot_type::<T>(index);
+
+        (self.id, TypeId::of::<T>(), StateSlot::Index(index))
+    }
+
+    #[cfg(debug_assertions)]
+    fn slot_record(&mut self) -> &mut SlotRecord {
+        let key = (self.id, TypeId::of::<SlotRecord>(), StateSlot::Index(0));
+        self.states
+            .entry(key)
+            .or_insert_with(|| {
+                Box::new(SlotRecord {
+                    slots: Vec::new(),
+                    complete: false,
+                })
+            })
+            .downcast_mut()
+            .unwrap()
+    }
+
+    #[cfg(debug_assertions)]
+    #[track_caller]
+    fn check_slot_type<T: Any>(&mut self, index: usize) {
+        let location = Location::caller();
+        let record = self.slot_record();
+
+        if let Some(&(previous_type, previous_location)) = record.slots.get(index) {
+            assert!(
+                previous_type == TypeId::of::<T>() && previous_location == location,
+                "State {} of type {} created at {} was created at {} before, states must be created in the same order on every build",
+                index,
+                type_name::<T>(),
+                location,
+                previous_location,
+            );
+        } else {
+            assert!(
+                !record.complete,
+                "State {} of type {} created at {} is new, states must be created in the same order on every build",
+                index,
+                type_name::<T>(),
+                location,
+            );
+            record.slots.push((TypeId::of::<T>(), location));
+        }
+    }
+
+    #[cfg(debug_assertions)]
+    fn check_slot_count(&mut self) {
+        let count = self.slot;
+        let key = (self.id, TypeId::of::<SlotRecord>(), StateSlot::Index(0));
+        if count == 0 && !self.states.contains_key(&key) {
+            return;
+        }
+        let record = self.slot_record();
+        assert!(
+            count == record.slots.len(),
+            "{} states were created instead of {}, states must be created in the same order on every build",
+            count,
+            record.slots.len(),
+        );
+        record.complete = true;
+    }
+
     pub fn try_get<T: Any>(&self, binding: Binding<T>) -> Option<&T> {
+        let resolved = resolve(self.states, binding.into());
+        self.dependencies.borrow_mut().add_connection(
+            self.reader,
+            resolved.map_or(binding.into(), |(_, root)| root),
+        );
+        resolved.and_then(|(state, _)| state.downcast_ref())
+    }
+
+    /// Like `get`, but returns the error instead of raising it, so a component can fail its build
+    /// with `?`.
+    pub fn require<T: Any>(&self, binding: Binding<T>) -> Result<&T, ViewError> {
+        self.try_get(binding)
+            .ok_or(ViewError::MissingState(type_name::<T>()))
+    }
+
+    #[track_caller]
+    pub fn get<T: Any>(&self, binding: Binding<T>) -> &T {
+        match self.require(binding) {
+            Ok(value) => value,
+            Err(error) => raise(error),
+        }
+    }
+
+    pub fn try_env<T: Any>(&self) -> Option<&T> {
+        let key = self.ancestors.iter().find_map(|&ancestor| {
+            let key = (
+                Some(ancestor),
+                TypeId::of::<EnvironmentValue<T>>(),
+                StateSlot::Index(0),
+            );
+            self.states.contains_key(&key).then_some(key)
+        })?;
         self.dependencies
             .borrow_mut()
-            .add_connection(self.id, binding.into());
+            .add_connection(self.reader, key);
         self.states
-            .get(&binding.into())
-            .and_then(|state| state.downcast_ref())
+            .get(&key)
+            .and_then(|state| state.downcast_ref::<EnvironmentValue<T>>())
+            .map(|EnvironmentValue(value)| value)
     }
 
-    pub fn get<T: Any>(&self, binding: Binding<T>) -> &T {
-        self.try_get(binding).expect("State doesn't exist")
+    pub fn require_env<T: Any>(&self) -> Result<&T, ViewError> {
+        self.try_env()
+            .ok_or(ViewError::MissingEnvironment(type_name::<T>()))
+    }
+
+    #[track_caller]
+    pub fn env<T: Any>(&self) -> &T {
+        match self.require_env() {
+            Ok(value) => value,
+            Err(error) => raise(error),
+        }
+    }
+
+    pub fn color_scheme(&self) -> ColorScheme {
+        self.get(Binding::<SystemColorScheme>::global()).0
+    }
+
+    pub fn sender<T: Any + Send>(&self, binding: Binding<T>) -> Sender<T> {
+        Sender::new(self.states, binding)
+    }
+
+    pub fn undoable<T: Any + Clone>(&mut self, binding: Binding<T>) {
+        History::with(self.states, |history, _| {
+            history.register::<T>(binding.into())
+        });
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.read_history(History::can_undo)
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.read_history(History::can_redo)
+    }
+
+    fn read_history(&self, f: impl FnOnce(&History) -> bool) -> bool {
+        self.dependencies
+            .borrow_mut()
+            .add_connection(self.reader, History::key());
+        self.states
+            .get(&History::key())
+            .and_then(|history| history.downcast_ref())
+            .is_some_and(f)
+    }
+}
+
+pub type StateRefMut<'a, T> = TrackedRefMut<'a, T, StateTracker<'a>>;
+
+pub struct StateTracker<'a> {
+    key: StateKey,
+    changes: &'a mut StateChanges,
+    history: Option<&'a mut History>,
+    // values behind a lens aren't the state itself, so those are snapshotted up front
+    snapshot_lazily: bool,
+}
+
+impl RefTracker for StateTracker<'_> {
+    fn accessed(&mut self) {}
+}
+
+impl<T: Any> RefMutTracker<T> for StateTracker<'_> {
+    fn mutating(&mut self, value: &T) {
+        if let (true, Some(history)) = (self.snapshot_lazily, &mut self.history) {
+            history.record(self.key, value);
+        }
+    }
+
+    fn accessed_mut(&mut self) {
+        self.changes.insert(self.key);
+        if let Some(history) = &mut self.history {
+            history.written(self.key);
+        }
     }
 }
 
@@ -69,57 +658,202 @@ pub struct ContextMut<'a> {
     id: Option<Id>,
     states: &'a mut States,
     changes: &'a mut StateChanges,
+    window: &'a mut WindowHandle,
 }
 
 impl ContextMut<'_> {
-    pub(crate) fn new<'a>(states: &'a mut States, changes: &'a mut StateChanges) -> ContextMut<'a> {
+    pub(crate) fn new<'a>(
+        states: &'a mut States,
+        changes: &'a mut StateChanges,
+        window: &'a mut WindowHandle,
+    ) -> ContextMut<'a> {
         ContextMut {
             id: None,
             states,
             changes,
+            window,
         }
     }
 
     pub(super) fn with_id<T>(&mut self, id: Id, f: impl FnOnce(&mut ContextMut) -> T) -> T {
-        f(&mut ContextMut {
+        let result = f(&mut ContextMut {
             id: Some(id),
             states: self.states,
             changes: self.changes,
-        })
+            window: self.window,
+        });
+        register_stores(self.states);
+        result
     }
 }
 
 impl ContextMut<'_> {
     pub fn try_get<T: Any>(&self, binding: Binding<T>) -> Option<&T> {
+        resolve(self.states, binding.into()).and_then(|(state, _)| state.downcast_ref())
+    }
+
+    pub fn try_get_mut<T: Any>(&mut self, binding: Binding<T>) -> Option<StateRefMut<'_, T>> {
+        register_stores(self.states);
+        let (key, getters) = lens_path(self.states, binding.into())?;
+        let [Some(state), history] = self.states.get_disjoint_mut([&key, &History::key()]) else {
+            return None;
+        };
+        let mut history = history.and_then(|history| history.downcast_mut::<History>());
+        if let (false, Some(history)) = (getters.is_empty(), &mut history) {
+            history.record(key, state.as_ref());
+        }
+        let value = follow_mut(state.as_mut(), &getters)?.downcast_mut()?;
+        Some(TrackedRefMut::new(
+            value,
+            StateTracker {
+                key,
+                changes: self.changes,
+                history,
+                snapshot_lazily: getters.is_empty(),
+            },
+        ))
+    }
+
+    #[track_caller]
+    pub fn get<T: Any>(&self, binding: Binding<T>) -> &T {
+        match self.try_get(binding) {
+            Some(value) => value,
+            None => raise(ViewError::MissingState(type_name::<T>())),
+        }
+    }
+
+    #[track_caller]
+    pub fn get_mut<T: Any>(&mut self, binding: Binding<T>) -> StateRefMut<'_, T> {
+        match self.try_get_mut(binding) {
+            Some(value) => value,
+            None => raise(ViewError::MissingState(type_name::<T>())),
+        }
+    }
+
+    pub fn set<T: Any + PartialEq>(&mut self, binding: Binding<T>, value: T) {
+        let mut state = self.get_mut(binding);
+        if *state != value {
+            *state = value;
+        }
+    }
+
+    pub fn window(&mut self) -> &mut WindowHandle {
+        self.window
+    }
+
+    pub fn sender<T: Any + Send>(&self, binding: Binding<T>) -> Sender<T> {
+        Sender::new(self.states, binding)
+    }
+
+    pub fn dispatch<M: Any>(&mut self, message: M) -> Result<(), ViewError> {
+        register_stores(self.states);
+        let (reducer, middleware) = Dispatcher::<M>::handlers(self.states)
+            .ok_or(ViewError::UnhandledMessage(type_name::<M>()))?;
+        dispatch_through(self, &middleware, &reducer, message);
+        Ok(())
+    }
+
+    pub fn transaction<T>(
+        &mut self,
+        name: impl Into<String>,
+        f: impl FnOnce(&mut ContextMut) -> T,
+    ) -> T {
+        let began = History::with(self.states, |history, _| history.begin(name.into()));
+        let result = f(self);
+        if began {
+            self.commit_transaction();
+        }
+        result
+    }
+
+    pub(crate) fn commit_transaction(&mut self) {
+        if !self.states.contains_key(&History::key()) {
+            return;
+        }
+        if History::with(self.states, |history, _| history.commit()) {
+            self.changes.insert(History::key());
+        }
+    }
+
+    pub(crate) fn clear_boundary(&mut self, id: Id) {
+        let key = boundary_key(Some(id));
+        if self.states.remove(&key).is_some() {
+            self.changes.insert(key);
+        }
+    }
+
+    pub fn undo(&mut self) -> bool {
+        let changes = &mut *self.changes;
+        let undone = History::with(self.states, |history, states| history.undo(states, changes));
+        if undone {
+            self.changes.insert(History::key());
+        }
+        undone
+    }
+
+    pub fn redo(&mut self) -> bool {
+        let changes = &mut *self.changes;
+        let redone = History::with(self.states, |history, states| history.redo(states, changes));
+        if redone {
+            self.changes.insert(History::key());
+        }
+        redone
+    }
+
+    pub fn undo_name(&self) -> Option<&str> {
         self.states
-            .get(&binding.into())
-            .and_then(|state| state.downcast_ref())
+            .get(&History::key())
+            .and_then(|history| history.downcast_ref::<History>())
+            .and_then(History::undo_name)
     }
 
-    pub fn try_get_mut<T: Any>(&mut self, binding: Binding<T>) -> Option<&mut T> {
-        self.changes.insert(binding.into());
+    pub fn redo_name(&self) -> Option<&str> {
         self.states
-            .get_mut(&binding.into())
-            .and_then(|state| state.downcast_mut())
+            .get(&History::key())
+            .and_then(|history| history.downcast_ref::<History>())
+            .and_then(History::redo_name)
     }
+}
 
-    pub fn get<T: Any>(&self, binding: Binding<T>) -> &T {
-        self.try_get(binding).expect("State doesn't exist")
+pub struct Cleanup(Option<Box<dyn FnOnce()>>);
+
+impl Cleanup {
+    pub fn new(cleanup: impl FnOnce() + 'static) -> Self {
+        Self(Some(Box::new(cleanup)))
     }
+}
 
-    pub fn get_mut<T: Any>(&mut self, binding: Binding<T>) -> &mut T {
-        self.try_get_mut(binding).expect("State doesn't exist")
+impl Drop for Cleanup {
+    fn drop(&mut self) {
+        if let Some(cleanup) = self.0.take() {
+            cleanup();
+        }
     }
 }
 
 pub struct Binding<T> {
     owner: Option<Id>,
+    slot: StateSlot,
     _phantom_data: PhantomData<T>,
 }
 
+impl<T> Binding<T> {
+    pub(crate) fn new(owner: Option<Id>, slot: StateSlot) -> Self {
+        Self {
+            owner,
+            slot,
+            _phantom_data: PhantomData,
+        }
+    }
+
+    pub(crate) fn global() -> Self {
+        Self::new(None, StateSlot::Index(0))
+    }
+}
+
 impl<T> PartialEq for Binding<T> {
     fn eq(&self, other: &Self) -> bool {
-        self.owner == other.owner
+        self.owner == other.owner && self.slot == other.slot
     }
 }
 
@@ -127,10 +861,7 @@ impl<T> Eq for Binding<T> {}
 
 impl<T> Clone for Binding<T> {
     fn clone(&self) -> Self {
-        Self {
-            owner: self.owner,
-            _phantom_data: self._phantom_data,
-        }
+        *self
     }
 }
 
@@ -138,6 +869,359 @@ impl<T> Copy for Binding<T> {}
 
 impl<T: Any> From<Binding<T>> for StateKey {
     fn from(binding: Binding<T>) -> StateKey {
-        (binding.owner, TypeId::of::<T>())
+        (binding.owner, TypeId::of::<T>(), binding.slot)
+    }
+}
+
+pub(crate) mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    pub(crate) struct TestStates {
+        pub(crate) states: States,
+        pub(crate) dependencies: StateDependencies,
+        pub(crate) changes: StateChanges,
+        pub(crate) window: WindowHandle,
+    }
+
+    impl TestStates {
+        pub(crate) fn new() -> Self {
+            Self {
+                states: States::new(),
+                dependencies: StateDependencies::new(),
+                changes: StateChanges::new(),
+                window: WindowHandle::new(),
+            }
+        }
+
+        pub(crate) fn context(&mut self) -> Context<'_> {
+            Context::new(&mut self.states, &mut self.dependencies, &mut self.changes)
+        }
+
+        pub(crate) fn context_mut(&mut self) -> ContextMut<'_> {
+            ContextMut::new(&mut self.states, &mut self.changes, &mut self.window)
+        }
+
+        // writes the value without recording a change, as if it came from outside the frame
+        pub(crate) fn poke<T: Any>(&mut self, binding: Binding<T>, value: T) {
+            *self
+                .states
+                .get_mut(&binding.into())
+                .unwrap()
+                .downcast_mut()
+                .unwrap() = value;
+        }
+    }
+
+    #[test]
+    fn states_by_call_order() {
+        let mut test = TestStates::new();
+        let mut context = test.context();
+
+        let build = |context: &mut Context, first, second| {
+            (context.state(move || first), context.state(move || second))
+        };
+
+        let (first, second) = context.with_id(Id::new(0, 0), &[], |context| build(context, 1, 2));
+        assert!(first != second);
+        assert_eq!(*context.get(first), 1);
+        assert_eq!(*context.get(second), 2);
+
+        let (first_again, second_again) =
+            context.with_id(Id::new(0, 0), &[], |context| build(context, 3, 4));
+        assert!(first == first_again);
+        assert!(second == second_again);
+        assert_eq!(*context.get(first_again), 1);
+        assert_eq!(*context.get(second_again), 2);
+    }
+
+    #[test]
+    fn states_by_key() {
+        let mut test = TestStates::new();
+        let mut context = test.context();
+
+        let (expanded, selected) = context.with_id(Id::new(0, 0), &[], |context| {
+            (
+                context.state_keyed("expanded", || false),
+                context.state_keyed("selected", || true),
+            )
+        });
+        assert!(!*context.get(expanded));
+        assert!(*context.get(selected));
+    }
+
+    #[test]
+    fn derived_recomputes_on_change() {
+        let mut test = TestStates::new();
+        let mut context = test.context();
+
+        let (a, sum) = context.with_id(Id::new(0, 0), &[], |context| {
+            let a = context.state(|| 1);
+            let b = context.state(|| 2);
+            let sum = context.derived(move |context| context.get(a) + context.get(b));
+            (a, sum)
+        });
+        let reader = context.with_id(Id::new(1, 0), &[], |context| *context.get(sum));
+        assert_eq!(reader, 3);
+
+        test.poke(a, 2);
+        let mut context = test.context();
+        let dirty_views = context.invalidate(vec![a.into()]);
+        assert_eq!(*context.get(sum), 4);
+        assert!(dirty_views.contains(&Id::new(1, 0)));
+        assert!(!dirty_views.contains(&Id::new(0, 0)));
+    }
+
+    #[test]
+    fn derived_computes_only_on_change() {
+        let mut test = TestStates::new();
+        let computed = Rc::new(Cell::new(0));
+
+        let build = |context: &mut Context, factor: i32| {
+            let computed = computed.clone();
+            context.with_id(Id::new(0, 0), &[], |context| {
+                let count = context.state(|| 1);
+                let product = context.derived_with(factor, move |context| {
+                    computed.set(computed.get() + 1);
+                    context.get(count) * factor
+                });
+                (count, product)
+            })
+        };
+        let mut context = test.context();
+        let (count, product) = build(&mut context, 2);
+        build(&mut context, 2);
+        assert_eq!(*context.get(product), 2);
+        assert_eq!(computed.get(), 1);
+        assert!(context.changes.is_empty());
+
+        test.poke(count, 2);
+        let mut context = test.context();
+        context.invalidate(vec![count.into()]);
+        build(&mut context, 2);
+        assert_eq!(*context.get(product), 4);
+        assert_eq!(computed.get(), 2);
+
+        build(&mut context, 3);
+        assert_eq!(*context.get(product), 6);
+        assert_eq!(computed.get(), 3);
+        assert!(context.changes.contains(&product.into()));
+    }
+
+    #[test]
+    fn derived_recomputes_with_environment() {
+        let mut test = TestStates::new();
+        let mut context = test.context();
+
+        let (parent, child) = (Id::new(0, 0), Id::new(1, 0));
+        context.with_id(parent, &[], |context| context.provide(10));
+        let (count, sum) = context.with_id(child, &[parent], |context| {
+            let count = context.state(|| 1);
+            let sum = context.derived(move |context| context.get(count) + context.env::<i32>());
+            (count, sum)
+        });
+
+        test.poke(count, 2);
+        let mut context = test.context();
+        context.invalidate(vec![count.into()]);
+        assert_eq!(*context.get(sum), 12);
+    }
+
+    #[test]
+    fn color_scheme_changes_invalidate_readers() {
+        let mut test = TestStates::new();
+        let system = Binding::<SystemColorScheme>::global();
+        test.states.insert(
+            system.into(),
+            Box::new(SystemColorScheme(ColorScheme::Light)),
+        );
+        let color_scheme = test
+            .context()
+            .with_id(Id::new(0, 0), &[], |context| context.color_scheme());
+        assert_eq!(color_scheme, ColorScheme::Light);
+
+        test.context_mut().get_mut(system).0 = ColorScheme::Dark;
+        let changed = test.changes.drain().collect::<Vec<_>>();
+        let mut context = test.context();
+        assert!(context.invalidate(changed).contains(&Id::new(0, 0)));
+        assert_eq!(context.color_scheme(), ColorScheme::Dark);
+    }
+
+    #[test]
+    fn changes_recorded_on_write() {
+        let mut test = TestStates::new();
+        let count = test
+            .context()
+            .with_id(Id::new(0, 0), &[], |context| context.state(|| 1));
+
+        let mut context = test.context_mut();
+        assert_eq!(*context.get_mut(count), 1);
+        context.set(count, 1);
+        assert!(context.changes.is_empty());
+
+        context.set(count, 2);
+        assert!(context.changes.contains(&count.into()));
+    }
+
+    #[test]
+    fn effects_scheduled() {
+        let mut test = TestStates::new();
+        let mut context = test.context();
+
+        let build = |context: &mut Context, dependency: i32| {
+            context.with_id(Id::new(0, 0), &[], |context| {
+                context.on_appear(|_| {});
+                context.effect(dependency, |_| {});
+                context.on_disappear(|_| {});
+            })
+        };
+        let pending = |context: &mut Context| {
+            context
+                .states
+                .remove(&PendingEffects::key())
+                .map_or(0, |pending| {
+                    let PendingEffects(effects) = *pending.downcast().unwrap();
+                    effects.len()
+                })
+        };
+
+        build(&mut context, 1);
+        assert_eq!(pending(&mut context), 2);
+        build(&mut context, 1);
+        assert_eq!(pending(&mut context), 0);
+        build(&mut context, 2);
+        assert_eq!(pending(&mut context), 1);
+
+        context.release(&HashSet::from([Id::new(0, 0)]));
+        assert_eq!(pending(&mut context), 1);
+    }
+
+    #[test]
+    fn lens_reads_and_writes_parent() {
+        let mut test = TestStates::new();
+        let mut context = test.context();
+
+        let (pair, second) = context.with_id(Id::new(0, 0), &[], |context| {
+            let pair = context.state(|| (1, 2));
+            let second = context.lens(pair, |pair| Some(&pair.1), |pair| Some(&mut pair.1));
+            (pair, second)
+        });
+        let value = context.with_id(Id::new(1, 0), &[], |context| *context.get(second));
+        assert_eq!(value, 2);
+        assert!(context
+            .invalidate(vec![pair.into()])
+            .contains(&Id::new(1, 0)));
+
+        let mut context = test.context_mut();
+        *context.get_mut(second) = 3;
+        assert_eq!(*context.get(pair), (1, 3));
+        assert!(context.changes.contains(&pair.into()));
+    }
+
+    #[test]
+    fn stale_lens_is_missing() {
+        let mut test = TestStates::new();
+        let (items, last) = test.context().with_id(Id::new(0, 0), &[], |context| {
+            let items = context.state(|| vec![1, 2]);
+            let last = context.lens(items, |items| items.get(1), |items| items.get_mut(1));
+            (items, last)
+        });
+
+        let mut context = test.context_mut();
+        context.get_mut(items).pop();
+        assert_eq!(context.try_get(last), None);
+        assert!(context.try_get_mut(last).is_none());
+    }
+
+    fn undoable_count(test: &mut TestStates) -> Binding<i32> {
+        test.context().with_id(Id::new(0, 0), &[], |context| {
+            let count = context.state(|| 1);
+            context.undoable(count);
+            count
+        })
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut test = TestStates::new();
+        let count = undoable_count(&mut test);
+
+        let mut context = test.context_mut();
+        *context.get_mut(count) = 2;
+        context.commit_transaction();
+        context.transaction("Add", |context| {
+            *context.get_mut(count) += 1;
+            *context.get_mut(count) += 1;
+        });
+        assert_eq!(*context.get(count), 4);
+        assert_eq!(context.undo_name(), Some("Add"));
+
+        assert!(context.undo());
+        assert_eq!(*context.get(count), 2);
+        assert!(context.undo());
+        assert_eq!(*context.get(count), 1);
+        assert!(!context.undo());
+
+        assert!(context.redo());
+        assert_eq!(*context.get(count), 2);
+        assert_eq!(context.redo_name(), Some("Add"));
+        *context.get_mut(count) = 5;
+        context.commit_transaction();
+        assert!(!context.redo());
+    }
+
+    #[test]
+    fn reads_through_get_mut_not_undoable() {
+        let mut test = TestStates::new();
+        let count = undoable_count(&mut test);
+
+        let mut context = test.context_mut();
+        *context.get_mut(count) = 2;
+        context.commit_transaction();
+        // the earlier write is still among the changes of the frame
+        context.transaction("Read", |context| assert_eq!(*context.get_mut(count), 2));
+        assert_eq!(context.undo_name(), None);
+
+        assert!(context.undo());
+        assert_eq!(*context.get(count), 1);
+        assert!(!context.undo());
+    }
+
+    #[test]
+    fn release_purges_registries() {
+        let mut test = TestStates::new();
+        let count = undoable_count(&mut test);
+        test.context().with_id(Id::new(1, 0), &[], |context| {
+            context.lens(count, |count| Some(count), |count| Some(count))
+        });
+
+        let mut context = test.context_mut();
+        *context.get_mut(count) = 2;
+        context.commit_transaction();
+
+        let mut context = test.context();
+        assert!(context.can_undo());
+        context.release(&HashSet::from([Id::new(0, 0)]));
+        assert!(!context.can_undo());
+        assert!(!test
+            .states
+            .keys()
+            .any(|&(_, type_id, _)| type_id == TypeId::of::<Lens>()));
+    }
+
+    #[test]
+    #[should_panic(expected = "same order")]
+    #[cfg(debug_assertions)]
+    fn states_out_of_order() {
+        let mut test = TestStates::new();
+        let mut context = test.context();
+
+        context.with_id(Id::new(0, 0), &[], |context| {
+            context.state(|| 1);
+            context.state(|| false);
+        });
+        context.with_id(Id::new(0, 0), &[], |context| {
+            context.state(|| false);
+        });
     }
 }
@@ -0,0 +1,52 @@
+use super::{
+    context::{ContextMut, StateChanges, StateKey, StateSlot, States},
+    window::WindowHandle,
+};
+use crate::utils::id_vec::Id;
+use std::any::TypeId;
+
+pub(crate) type Effect = Box<dyn FnOnce(&mut ContextMut)>;
+
+pub(crate) struct PendingEffects(pub(crate) Vec<(Option<Id>, Effect)>);
+
+pub(crate) struct Appeared;
+
+pub(crate) struct Disappear(pub(crate) Effect);
+
+pub(crate) struct EffectDependencies<D>(pub(crate) D);
+
+impl PendingEffects {
+    pub(crate) fn key() -> StateKey {
+        (None, TypeId::of::<PendingEffects>(), StateSlot::Index(0))
+    }
+
+    pub(crate) fn push(states: &mut States, id: Option<Id>, effect: Effect) {
+        let PendingEffects(effects) = states
+            .entry(Self::key())
+            .or_insert_with(|| Box::new(PendingEffects(Vec::new())))
+            .downcast_mut()
+            .unwrap();
+        effects.push((id, effect));
+    }
+}
+
+pub(crate) fn run_effects(
+    states: &mut States,
+    changes: &mut StateChanges,
+    window: &mut WindowHandle,
+) -> bool {
+    let Some(pending) = states.remove(&PendingEffects::key()) else {
+        return false;
+    };
+    let PendingEffects(effects) = *pending.downcast().unwrap();
+
+    let mut context = ContextMut::new(states, changes, window);
+    for (id, effect) in effects {
+        match id {
+            Some(id) => context.with_id(id, effect),
+            None => effect(&mut context),
+        }
+    }
+    context.commit_transaction();
+    true
+}
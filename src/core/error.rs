@@ -0,0 +1,113 @@
+use super::context::{StateKey, StateSlot, States};
+use crate::utils::id_vec::Id;
+use std::{
+    any::{Any, TypeId},
+    cell::RefCell,
+    error::Error,
+    fmt,
+    panic::{self, Location},
+    rc::Rc,
+    sync::Once,
+    thread,
+};
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum ViewError {
+    MissingState(&'static str),
+    MissingEnvironment(&'static str),
+    UnhandledMessage(&'static str),
+    Failed(String),
+    Panicked(String),
+}
+
+impl ViewError {
+    pub fn failed(message: impl Into<String>) -> Self {
+        Self::Failed(message.into())
+    }
+
+    pub(crate) fn from_panic(payload: Box<dyn Any + Send>) -> Self {
+        match payload.downcast::<ViewError>() {
+            Ok(error) => *error,
+            Err(payload) => Self::Panicked(
+                payload
+                    .downcast_ref::<&str>()
+                    .map(|message| message.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "Unknown panic".into()),
+            ),
+        }
+    }
+}
+
+impl fmt::Display for ViewError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::MissingState(type_name) => write!(f, "State of type {} doesn't exist", type_name),
+            Self::MissingEnvironment(type_name) => {
+                write!(f, "Environment value of type {} doesn't exist", type_name)
+            }
+            Self::UnhandledMessage(type_name) => {
+                write!(
+                    f,
+                    "No reducer is registered for messages of type {}",
+                    type_name
+                )
+            }
+            Self::Failed(message) => write!(f, "{}", message),
+            Self::Panicked(message) => write!(f, "Panicked: {}", message),
+        }
+    }
+}
+
+impl Error for ViewError {}
+
+pub(crate) struct BoundaryError(pub(crate) ViewError);
+
+pub(crate) fn boundary_key(id: Option<Id>) -> StateKey {
+    (id, TypeId::of::<BoundaryError>(), StateSlot::Index(0))
+}
+
+pub(crate) fn fail_boundary(states: &mut States, id: Id, error: ViewError) {
+    states.insert(boundary_key(Some(id)), Box::new(BoundaryError(error)));
+}
+
+type ErrorHook = Rc<dyn Fn(&ViewError)>;
+
+thread_local! {
+    static ERROR_HOOK: RefCell<Option<ErrorHook>> = const { RefCell::new(None) };
+}
+
+static PANIC_HOOK: Once = Once::new();
+
+// Errors unwind to the nearest error boundary as panics carrying the `ViewError`, so the panic
+// hook is extended to print them like string panics instead of as `Box<dyn Any>`
+#[track_caller]
+pub(crate) fn raise(error: ViewError) -> ! {
+    PANIC_HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let Some(error) = info.payload().downcast_ref::<ViewError>() else {
+                return previous(info);
+            };
+            let thread = thread::current();
+            let location = info.location().map_or(String::new(), Location::to_string);
+            eprintln!(
+                "thread '{}' panicked at {}:\n{}",
+                thread.name().unwrap_or("<unnamed>"),
+                location,
+                error
+            );
+        }));
+    });
+    panic::panic_any(error)
+}
+
+pub(crate) fn set_error_hook(hook: ErrorHook) {
+    ERROR_HOOK.with_borrow_mut(|error_hook| *error_hook = Some(hook));
+}
+
+pub(crate) fn report(error: &ViewError) {
+    if let Some(hook) = ERROR_HOOK.with_borrow(|hook| hook.clone()) {
+        hook(error);
+    }
+}
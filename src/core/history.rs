@@ -0,0 +1,182 @@
+use super::context::{StateChanges, StateKey, StateSlot, States};
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    rc::Rc,
+};
+
+type Snapshotter = Rc<dyn Fn(&dyn Any) -> Box<dyn Any>>;
+
+pub(crate) struct History {
+    snapshotters: HashMap<StateKey, Snapshotter>,
+    current: Option<Transaction>,
+    undo_stack: Vec<Transaction>,
+    redo_stack: Vec<Transaction>,
+}
+
+struct Transaction {
+    name: Option<String>,
+    snapshots: Vec<(StateKey, Box<dyn Any>)>,
+    written: Vec<StateKey>,
+}
+
+impl Transaction {
+    fn new(name: Option<String>) -> Self {
+        Self {
+            name,
+            snapshots: Vec::new(),
+            written: Vec::new(),
+        }
+    }
+}
+
+impl History {
+    pub(crate) fn key() -> StateKey {
+        (None, TypeId::of::<History>(), StateSlot::Index(0))
+    }
+
+    pub(crate) fn with<R>(
+        states: &mut States,
+        f: impl FnOnce(&mut History, &mut States) -> R,
+    ) -> R {
+        let mut history = states
+            .remove(&Self::key())
+            .unwrap_or_else(|| Box::new(History::new()));
+        let result = f(history.downcast_mut().unwrap(), states);
+        states.insert(Self::key(), history);
+        result
+    }
+
+    fn new() -> Self {
+        Self {
+            snapshotters: HashMap::new(),
+            current: None,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+        }
+    }
+
+    pub(crate) fn register<T: Any + Clone>(&mut self, key: StateKey) {
+        self.snapshotters.entry(key).or_insert_with(|| {
+            Rc::new(|value: &dyn Any| Box::new(value.downcast_ref::<T>().unwrap().clone()))
+        });
+    }
+
+    pub(crate) fn release(&mut self, released: &[StateKey]) {
+        self.snapshotters.retain(|key, _| !released.contains(key));
+        let stacks = self.undo_stack.iter_mut().chain(self.redo_stack.iter_mut());
+        for transaction in stacks.chain(self.current.as_mut()) {
+            transaction
+                .snapshots
+                .retain(|(key, _)| !released.contains(key));
+        }
+        self.undo_stack
+            .retain(|transaction| !transaction.snapshots.is_empty());
+        self.redo_stack
+            .retain(|transaction| !transaction.snapshots.is_empty());
+    }
+
+    pub(crate) fn record(&mut self, key: StateKey, value: &dyn Any) {
+        let Some(snapshotter) = self.snapshotters.get(&key) else {
+            return;
+        };
+        let transaction = self.current.get_or_insert_with(|| Transaction::new(None));
+        if !transaction
+            .snapshots
+            .iter()
+            .any(|(recorded, _)| *recorded == key)
+        {
+            transaction.snapshots.push((key, snapshotter(value)));
+        }
+    }
+
+    pub(crate) fn begin(&mut self, name: String) -> bool {
+        if self.current.is_some() {
+            return false;
+        }
+        self.current = Some(Transaction::new(Some(name)));
+        true
+    }
+
+    pub(crate) fn written(&mut self, key: StateKey) {
+        if let Some(transaction) = &mut self.current {
+            if !transaction.written.contains(&key) {
+                transaction.written.push(key);
+            }
+        }
+    }
+
+    // Snapshots of bindings that were only read through `get_mut` are dropped
+    pub(crate) fn commit(&mut self) -> bool {
+        match self.current.take() {
+            Some(mut transaction) => {
+                let written = std::mem::take(&mut transaction.written);
+                transaction
+                    .snapshots
+                    .retain(|(key, _)| written.contains(key));
+                if transaction.snapshots.is_empty() {
+                    return false;
+                }
+                self.undo_stack.push(transaction);
+                self.redo_stack.clear();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn undo(&mut self, states: &mut States, changes: &mut StateChanges) -> bool {
+        self.commit();
+        let Some(transaction) = self.undo_stack.pop() else {
+            return false;
+        };
+        let transaction = Self::restore(transaction, states, changes);
+        self.redo_stack.push(transaction);
+        true
+    }
+
+    pub(crate) fn redo(&mut self, states: &mut States, changes: &mut StateChanges) -> bool {
+        self.commit();
+        let Some(transaction) = self.redo_stack.pop() else {
+            return false;
+        };
+        let transaction = Self::restore(transaction, states, changes);
+        self.undo_stack.push(transaction);
+        true
+    }
+
+    pub(crate) fn undo_name(&self) -> Option<&str> {
+        self.undo_stack
+            .last()
+            .and_then(|transaction| transaction.name.as_deref())
+    }
+
+    pub(crate) fn redo_name(&self) -> Option<&str> {
+        self.redo_stack
+            .last()
+            .and_then(|transaction| transaction.name.as_deref())
+    }
+
+    pub(crate) fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty() || self.current.is_some()
+    }
+
+    pub(crate) fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    // Swaps the snapshots with the current values so the same transaction can be applied in reverse
+    fn restore(
+        mut transaction: Transaction,
+        states: &mut States,
+        changes: &mut StateChanges,
+    ) -> Transaction {
+        for (key, snapshot) in transaction.snapshots.iter_mut() {
+            if let Some(state) = states.get_mut(key) {
+                std::mem::swap(state, snapshot);
+                changes.insert(*key);
+            }
+        }
+        transaction
+    }
+}
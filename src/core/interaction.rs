@@ -5,6 +5,17 @@ pub enum Interaction {
     MouseMove(Vec2),
     MouseDown(Vec2),
     MouseUp(Vec2),
+    Lifecycle(Lifecycle),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Lifecycle {
+    CloseRequested,
+    Focused(bool),
+    Occluded(bool),
+    Suspended,
+    Resumed,
+    Exiting,
 }
 
 impl Interaction {
@@ -13,6 +24,7 @@ impl Interaction {
             Self::MouseMove(position) => Self::MouseMove(position - translation),
             Self::MouseDown(position) => Self::MouseDown(position - translation),
             Self::MouseUp(position) => Self::MouseUp(position - translation),
+            Self::Lifecycle(lifecycle) => Self::Lifecycle(lifecycle),
         }
     }
 }
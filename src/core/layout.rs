@@ -1,7 +1,16 @@
 use crate::math::Vec2;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub struct Layout {
     pub position: Vec2,
     pub size: Vec2,
 }
+
+impl Layout {
+    pub fn lerp(self, other: Self, t: f32) -> Self {
+        Self {
+            position: self.position.lerp(other.position, t),
+            size: self.size.lerp(other.size, t),
+        }
+    }
+}
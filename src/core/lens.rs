@@ -0,0 +1,75 @@
+use super::context::{StateKey, States};
+use crate::utils::id_vec::Id;
+use std::{
+    any::{Any, TypeId},
+    collections::HashSet,
+    rc::Rc,
+};
+
+pub(crate) type LensGet = Rc<dyn Fn(&dyn Any) -> Option<&dyn Any>>;
+pub(crate) type LensGetMut = Rc<dyn Fn(&mut dyn Any) -> Option<&mut dyn Any>>;
+
+pub(crate) struct Lens {
+    pub(crate) parent: StateKey,
+    pub(crate) get: LensGet,
+    pub(crate) get_mut: LensGetMut,
+}
+
+fn lens(states: &States, (owner, _, slot): StateKey) -> Option<&Lens> {
+    states
+        .get(&(owner, TypeId::of::<Lens>(), slot))
+        .and_then(|state| state.downcast_ref())
+}
+
+// Lenses of other views pointing into the states of released owners, directly or through
+// other such lenses
+pub(crate) fn stale_lenses(states: &States, owners: &HashSet<Id>) -> Vec<StateKey> {
+    let mut stale = Vec::<StateKey>::new();
+    loop {
+        let found = states
+            .iter()
+            .filter(|(key, _)| key.1 == TypeId::of::<Lens>() && !stale.contains(key))
+            .filter(|(&(owner, _, _), _)| !owner.is_some_and(|owner| owners.contains(&owner)))
+            .filter_map(|(&key, state)| {
+                let (owner, _, slot) = state.downcast_ref::<Lens>()?.parent;
+                let released = owner.is_some_and(|owner| owners.contains(&owner))
+                    || stale.contains(&(owner, TypeId::of::<Lens>(), slot));
+                released.then_some(key)
+            })
+            .collect::<Vec<_>>();
+        if found.is_empty() {
+            return stale;
+        }
+        stale.extend(found);
+    }
+}
+
+// Follows lenses down to the state that actually holds the value, returning its key too
+pub(crate) fn resolve(states: &States, key: StateKey) -> Option<(&dyn Any, StateKey)> {
+    if let Some(state) = states.get(&key) {
+        return Some((state.as_ref(), key));
+    }
+    let lens = lens(states, key)?;
+    let (parent, root) = resolve(states, lens.parent)?;
+    Some(((lens.get)(parent)?, root))
+}
+
+// The key of the state that actually holds the value and the getters leading from it to the value
+pub(crate) fn lens_path(states: &States, key: StateKey) -> Option<(StateKey, Vec<LensGetMut>)> {
+    if states.contains_key(&key) {
+        return Some((key, Vec::new()));
+    }
+    let lens = lens(states, key)?;
+    let (root, mut getters) = lens_path(states, lens.parent)?;
+    getters.push(lens.get_mut.clone());
+    Some((root, getters))
+}
+
+pub(crate) fn follow_mut<'a>(
+    value: &'a mut dyn Any,
+    getters: &[LensGetMut],
+) -> Option<&'a mut dyn Any> {
+    getters
+        .iter()
+        .try_fold(value, |value, get_mut| get_mut(value))
+}
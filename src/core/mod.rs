@@ -1,20 +1,34 @@
 #![allow(unused)]
+pub mod animation;
 pub mod app;
 pub mod constraints;
 pub mod context;
+pub mod effect;
+pub mod error;
+pub mod history;
 pub mod interaction;
 pub mod layout;
+pub mod lens;
+pub mod persistence;
+pub mod sender;
+pub mod store;
 pub mod view;
 pub mod view_tree;
+pub mod window;
 
 pub mod prelude {
     pub use super::{
-        app::{App, WindowBackground, WindowOptions},
+        animation::{Curve, Easing},
+        app::{App, AppOptions, WindowBackground, WindowOptions},
         constraints::{Constraint, Constraints},
-        context::{Binding, Context, ContextMut},
-        interaction::Interaction,
+        context::{Binding, Cleanup, Context, ContextMut},
+        error::ViewError,
+        interaction::{Interaction, Lifecycle},
         layout::Layout,
+        sender::Sender,
+        store::Store,
         view::View,
-        view_tree::{ViewDrawer, ViewInteractor, ViewSizer},
+        view_tree::{DumpOptions, ViewDrawer, ViewInteractor, ViewSizer},
+        window::{ColorScheme, WindowHandle},
     };
 }
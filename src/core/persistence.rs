@@ -0,0 +1,212 @@
+use super::{
+    context::{StateKey, StateSlot, States},
+    error::{report, ViewError},
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+type Serializer = Rc<dyn Fn(&dyn Any) -> Option<Value>>;
+
+pub(crate) struct Persistence {
+    path: Option<PathBuf>,
+    values: Map<String, Value>,
+    serializers: HashMap<StateKey, (&'static str, Serializer)>,
+    dirty: bool,
+}
+
+impl Persistence {
+    pub(crate) fn key() -> StateKey {
+        (None, TypeId::of::<Persistence>(), StateSlot::Index(0))
+    }
+
+    pub(crate) fn load(data_dir: &Path) -> Self {
+        let path = data_dir.join("state.json");
+        let values = fs::read_to_string(&path)
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default();
+        Self {
+            path: Some(path),
+            values,
+            serializers: HashMap::new(),
+            dirty: false,
+        }
+    }
+
+    pub(crate) fn with<R>(
+        states: &mut States,
+        f: impl FnOnce(&mut Persistence, &mut States) -> R,
+    ) -> R {
+        let mut persistence = states.remove(&Self::key()).unwrap_or_else(|| {
+            Box::new(Persistence {
+                path: None,
+                values: Map::new(),
+                serializers: HashMap::new(),
+                dirty: false,
+            })
+        });
+        let result = f(persistence.downcast_mut().unwrap(), states);
+        states.insert(Self::key(), persistence);
+        result
+    }
+
+    pub(crate) fn restore<T: Any + Serialize + DeserializeOwned>(
+        &mut self,
+        key: StateKey,
+        name: &'static str,
+    ) -> Option<T> {
+        assert!(
+            !self
+                .serializers
+                .iter()
+                .any(|(&other, &(other_name, _))| other_name == name && other != key),
+            "Persistent state {} is used with different types",
+            name,
+        );
+        self.serializers.insert(
+            key,
+            (
+                name,
+                Rc::new(|value: &dyn Any| {
+                    value
+                        .downcast_ref::<T>()
+                        .and_then(|value| serde_json::to_value(value).ok())
+                }),
+            ),
+        );
+        self.values
+            .get(name)
+            .and_then(|value| T::deserialize(value).ok())
+    }
+
+    pub(crate) fn release(&mut self, released: &[StateKey]) {
+        self.serializers.retain(|key, _| !released.contains(key));
+    }
+
+    pub(crate) fn mark_changed(&mut self, changed: &[StateKey]) {
+        if changed.iter().any(|key| self.serializers.contains_key(key)) {
+            self.dirty = true;
+        }
+    }
+
+    pub(crate) fn save(&mut self, states: &States) -> io::Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+
+        for (key, (name, serialize)) in self.serializers.iter() {
+            if let Some(value) = states.get(key).and_then(|state| serialize(state.as_ref())) {
+                self.values.insert(name.to_string(), value);
+            }
+        }
+
+        // stays dirty when writing fails so the next save tries again
+        if let Some(path) = &self.path {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, serde_json::to_string_pretty(&self.values)?)?;
+        }
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+pub(crate) fn save_persistent_states(states: &mut States) {
+    if !states.contains_key(&Persistence::key()) {
+        return;
+    }
+    if let Err(error) = Persistence::with(states, |persistence, states| persistence.save(states)) {
+        report(&ViewError::failed(format!(
+            "Failed to save persistent state: {}",
+            error
+        )));
+    }
+}
+
+pub(crate) fn default_app_id() -> String {
+    std::env::current_exe()
+        .ok()
+        .and_then(|path| Some(path.file_stem()?.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| "flux".into())
+}
+
+pub(crate) fn default_data_dir(app_name: &str) -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(app_name)
+}
+
+mod tests {
+    use super::*;
+    use crate::core::context::tests::TestStates;
+
+    fn load(data_dir: &Path) -> TestStates {
+        let mut test = TestStates::new();
+        test.states
+            .insert(Persistence::key(), Box::new(Persistence::load(data_dir)));
+        test
+    }
+
+    #[test]
+    fn restores_saved_values() {
+        let data_dir =
+            std::env::temp_dir().join(format!("flux-persistence-{}", std::process::id()));
+
+        let mut test = load(&data_dir);
+        let count = test.context().persistent_state("count", || 1);
+        test.poke(count, 2);
+        Persistence::with(&mut test.states, |persistence, _| {
+            persistence.mark_changed(&[count.into()])
+        });
+        save_persistent_states(&mut test.states);
+
+        let mut test = load(&data_dir);
+        let mut context = test.context();
+        let count = context.persistent_state("count", || 1);
+        assert_eq!(*context.get(count), 2);
+
+        fs::remove_dir_all(data_dir).unwrap();
+    }
+
+    #[test]
+    fn retries_failed_saves() {
+        let data_dir =
+            std::env::temp_dir().join(format!("flux-persistence-retry-{}", std::process::id()));
+        // a file in place of the data directory makes writing fail
+        fs::write(&data_dir, "").unwrap();
+
+        let mut test = load(&data_dir);
+        let count = test.context().persistent_state("count", || 1);
+        let states = &mut test.states;
+        Persistence::with(states, |persistence, _| {
+            persistence.mark_changed(&[count.into()])
+        });
+        save_persistent_states(states);
+        assert!(Persistence::with(states, |persistence, _| persistence.dirty));
+
+        fs::remove_file(&data_dir).unwrap();
+        save_persistent_states(states);
+        assert!(!Persistence::with(states, |persistence, _| persistence.dirty));
+        assert!(data_dir.join("state.json").exists());
+
+        fs::remove_dir_all(data_dir).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "different types")]
+    fn rejects_names_with_different_types() {
+        let mut test = TestStates::new();
+        let mut context = test.context();
+
+        context.persistent_state("count", || 1);
+        context.persistent_state("count", || 1.0);
+    }
+}
@@ -0,0 +1,109 @@
+use super::context::{Binding, ContextMut, StateKey, StateSlot, States};
+use std::{
+    any::{Any, TypeId},
+    marker::PhantomData,
+};
+use winit::event_loop::EventLoopProxy;
+
+pub(crate) enum AppEvent {
+    Update(Box<dyn FnOnce(&mut ContextMut) + Send>),
+}
+
+pub(crate) struct EventProxy(pub(crate) EventLoopProxy<AppEvent>);
+
+impl EventProxy {
+    pub(crate) fn key() -> StateKey {
+        (None, TypeId::of::<EventProxy>(), StateSlot::Index(0))
+    }
+}
+
+// Updates sent without a running event loop, or after it exited, are dropped
+pub struct Sender<T> {
+    binding: Binding<T>,
+    proxy: Option<EventLoopProxy<AppEvent>>,
+    _phantom_data: PhantomData<fn(T)>,
+}
+
+impl<T: Any + Send> Sender<T> {
+    pub(crate) fn new(states: &States, binding: Binding<T>) -> Self {
+        Self {
+            binding,
+            proxy: states
+                .get(&EventProxy::key())
+                .and_then(|state| state.downcast_ref::<EventProxy>())
+                .map(|EventProxy(proxy)| proxy.clone()),
+            _phantom_data: PhantomData,
+        }
+    }
+
+    pub fn send(&self, value: T) {
+        self.post(self.set_event(value));
+    }
+
+    pub fn update(&self, f: impl FnOnce(&mut T) + Send + 'static) {
+        self.post(self.update_event(f));
+    }
+
+    fn post(&self, event: AppEvent) {
+        if let Some(proxy) = &self.proxy {
+            let _ = proxy.send_event(event);
+        }
+    }
+
+    fn set_event(&self, value: T) -> AppEvent {
+        self.update_event(move |state| *state = value)
+    }
+
+    // a state released before the event arrives is skipped
+    fn update_event(&self, f: impl FnOnce(&mut T) + Send + 'static) -> AppEvent {
+        let binding = self.binding;
+        AppEvent::Update(Box::new(move |context| {
+            if let Some(mut state) = context.try_get_mut(binding) {
+                f(&mut state);
+            }
+        }))
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            binding: self.binding,
+            proxy: self.proxy.clone(),
+            _phantom_data: PhantomData,
+        }
+    }
+}
+
+mod tests {
+    use super::*;
+    use crate::core::context::tests::TestStates;
+
+    #[test]
+    fn sender_is_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<Sender<Vec<String>>>();
+    }
+
+    #[test]
+    fn updates_write_through_context() {
+        let mut test = TestStates::new();
+        let count = test.context().state(|| 1);
+        let sender = test.context().sender(count);
+
+        let AppEvent::Update(update) = sender.update_event(|count| *count += 1);
+        update(&mut test.context_mut());
+        assert_eq!(*test.context_mut().get(count), 2);
+        assert!(test.changes.contains(&count.into()));
+
+        let AppEvent::Update(send) = sender.set_event(5);
+        send(&mut test.context_mut());
+        assert_eq!(*test.context_mut().get(count), 5);
+
+        test.states.remove(&count.into());
+        test.changes.clear();
+        let AppEvent::Update(update) = sender.update_event(|count| *count += 1);
+        update(&mut test.context_mut());
+        assert!(test.changes.is_empty());
+    }
+}
@@ -0,0 +1,271 @@
+use super::context::{Binding, ContextMut, StateKey, StateSlot, States};
+use std::{
+    any::{type_name, Any, TypeId},
+    cell::{Cell, RefCell},
+    marker::PhantomData,
+    rc::Rc,
+    sync::OnceLock,
+    thread::{self, ThreadId},
+};
+
+// Stores are registered from thread locals, so they only work on the thread running the app
+pub struct Store<T> {
+    binding: Binding<T>,
+    _not_send: PhantomData<Rc<()>>,
+}
+
+pub(crate) struct Subscribers(pub(crate) Vec<Subscriber>);
+
+static APP_THREAD: OnceLock<ThreadId> = OnceLock::new();
+
+pub(crate) type Subscriber = Rc<dyn Fn(&dyn Any)>;
+pub(crate) type Reducer<M> = Rc<dyn Fn(&mut ContextMut, M)>;
+pub(crate) type Middleware<M> = Rc<dyn Fn(M, &mut dyn FnMut(M))>;
+type Registration = Box<dyn FnOnce(&mut States)>;
+
+pub(crate) struct Dispatcher<M> {
+    reducer: Option<Reducer<M>>,
+    middleware: Vec<Middleware<M>>,
+}
+
+thread_local! {
+    static NEXT_STORE: Cell<usize> = const { Cell::new(0) };
+    static PENDING_VALUES: RefCell<Vec<(StateKey, Box<dyn Any>)>> = RefCell::new(Vec::new());
+    static PENDING_SUBSCRIBERS: RefCell<Vec<(StateKey, Subscriber)>> = RefCell::new(Vec::new());
+    static PENDING_DISPATCHERS: RefCell<Vec<Registration>> = RefCell::new(Vec::new());
+}
+
+impl<T: Any> Store<T> {
+    pub fn new(value: T) -> Self {
+        assert!(
+            APP_THREAD
+                .get()
+                .is_none_or(|&app_thread| app_thread == thread::current().id()),
+            "Stores must be created on the thread running the app"
+        );
+        let index = NEXT_STORE.with(|next| next.replace(next.get() + 1));
+        let store = Self {
+            binding: Binding::new(None, StateSlot::Global(index)),
+            _not_send: PhantomData,
+        };
+        PENDING_VALUES.with_borrow_mut(|values| values.push((store.key(), Box::new(value))));
+        store
+    }
+
+    pub fn binding(&self) -> Binding<T> {
+        self.binding
+    }
+
+    pub fn subscribe(&self, subscriber: impl Fn(&T) + 'static) {
+        let subscriber = Rc::new(move |value: &dyn Any| {
+            if let Some(value) = value.downcast_ref() {
+                subscriber(value);
+            }
+        });
+        let (owner, _, slot) = self.key();
+        PENDING_SUBSCRIBERS.with_borrow_mut(|subscribers| {
+            subscribers.push(((owner, TypeId::of::<Subscribers>(), slot), subscriber))
+        });
+    }
+
+    /// Every message type is handled by a single reducer, registering a second one in the same
+    /// app panics once the stores are registered.
+    pub fn reducer<M: Any>(&self, reducer: impl Fn(&mut T, M) + 'static) {
+        let binding = self.binding;
+        let reducer: Reducer<M> = Rc::new(move |context, message| {
+            reducer(&mut context.get_mut(binding), message);
+        });
+        PENDING_DISPATCHERS.with_borrow_mut(|dispatchers| {
+            dispatchers.push(Box::new(move |states| {
+                let dispatcher = Dispatcher::<M>::get_or_insert(states);
+                assert!(
+                    dispatcher.reducer.is_none(),
+                    "A reducer is already registered for messages of type {}",
+                    type_name::<M>(),
+                );
+                dispatcher.reducer = Some(reducer);
+            }))
+        });
+    }
+
+    // Middleware runs in registration order and passes the message on by calling `next`
+    pub fn middleware<M: Any>(&self, middleware: impl Fn(M, &mut dyn FnMut(M)) + 'static) {
+        let middleware: Middleware<M> = Rc::new(middleware);
+        PENDING_DISPATCHERS.with_borrow_mut(|dispatchers| {
+            dispatchers.push(Box::new(move |states| {
+                Dispatcher::<M>::get_or_insert(states)
+                    .middleware
+                    .push(middleware);
+            }))
+        });
+    }
+
+    fn key(&self) -> StateKey {
+        self.binding.into()
+    }
+}
+
+impl<M: Any> Dispatcher<M> {
+    fn key() -> StateKey {
+        (None, TypeId::of::<Dispatcher<M>>(), StateSlot::Index(0))
+    }
+
+    pub(crate) fn handlers(states: &States) -> Option<(Reducer<M>, Vec<Middleware<M>>)> {
+        let dispatcher = states
+            .get(&Self::key())
+            .and_then(|state| state.downcast_ref::<Self>())?;
+        let reducer = dispatcher.reducer.clone()?;
+        Some((reducer, dispatcher.middleware.clone()))
+    }
+
+    fn get_or_insert(states: &mut States) -> &mut Self {
+        states
+            .entry(Self::key())
+            .or_insert_with(|| {
+                Box::new(Dispatcher::<M> {
+                    reducer: None,
+                    middleware: Vec::new(),
+                })
+            })
+            .downcast_mut()
+            .unwrap()
+    }
+}
+
+pub(crate) fn dispatch_through<M>(
+    context: &mut ContextMut,
+    middleware: &[Middleware<M>],
+    reducer: &Reducer<M>,
+    message: M,
+) {
+    match middleware.split_first() {
+        Some((first, rest)) => first(message, &mut |message| {
+            dispatch_through(context, rest, reducer, message)
+        }),
+        None => reducer(context, message),
+    }
+}
+
+impl<T> PartialEq for Store<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.binding == other.binding
+    }
+}
+
+impl<T> Eq for Store<T> {}
+
+impl<T> Clone for Store<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Store<T> {}
+
+impl<T> From<Store<T>> for Binding<T> {
+    fn from(store: Store<T>) -> Binding<T> {
+        store.binding
+    }
+}
+
+pub(crate) fn set_app_thread() {
+    APP_THREAD.get_or_init(|| thread::current().id());
+}
+
+// Called whenever a context is available, so a store created during a build or in a handler
+// can be used right after
+pub(crate) fn register_stores(states: &mut States) {
+    PENDING_VALUES.with_borrow_mut(|values| states.extend(values.drain(..)));
+    PENDING_DISPATCHERS.with_borrow_mut(|dispatchers| {
+        for register in dispatchers.drain(..) {
+            register(states);
+        }
+    });
+    PENDING_SUBSCRIBERS.with_borrow_mut(|subscribers| {
+        for (key, subscriber) in subscribers.drain(..) {
+            let Subscribers(subscribers) = states
+                .entry(key)
+                .or_insert_with(|| Box::new(Subscribers(Vec::new())))
+                .downcast_mut()
+                .unwrap();
+            subscribers.push(subscriber);
+        }
+    });
+}
+
+mod tests {
+    use super::*;
+    use crate::{
+        core::{context::tests::TestStates, error::ViewError},
+        utils::id_vec::Id,
+    };
+
+    enum Message {
+        Add(i32),
+    }
+
+    #[test]
+    fn dispatch_through_middleware() {
+        let store = Store::new(0);
+        store.reducer(|count: &mut i32, Message::Add(amount)| *count += amount);
+
+        let log = Rc::new(RefCell::new(Vec::new()));
+        store.middleware({
+            let log = log.clone();
+            move |Message::Add(amount), next| {
+                log.borrow_mut().push(amount);
+                next(Message::Add(amount * 2));
+            }
+        });
+
+        let mut test = TestStates::new();
+        let mut context = test.context_mut();
+        context.dispatch(Message::Add(2)).unwrap();
+        assert_eq!(*context.get(store.binding()), 4);
+        assert_eq!(*log.borrow(), vec![2]);
+        assert!(test.changes.contains(&store.key()));
+    }
+
+    #[test]
+    fn registers_stores_created_while_building() {
+        let mut test = TestStates::new();
+        let mut context = test.context();
+
+        let store = context.with_id(Id::new(0, 0), &[], |_| Store::new(1));
+        assert_eq!(context.try_get(store.binding()), Some(&1));
+    }
+
+    #[test]
+    fn unhandled_messages_fail() {
+        let mut test = TestStates::new();
+        let mut context = test.context_mut();
+        assert_eq!(
+            context.dispatch(Message::Add(1)),
+            Err(ViewError::UnhandledMessage(type_name::<Message>()))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "already registered")]
+    fn duplicate_reducers_rejected() {
+        let first = Store::new(0);
+        let second = Store::new(0);
+        first.reducer(|count: &mut i32, Message::Add(amount)| *count += amount);
+        second.reducer(|count: &mut i32, Message::Add(amount)| *count -= amount);
+        register_stores(&mut TestStates::new().states);
+    }
+
+    #[test]
+    fn reducers_registered_per_app() {
+        let first = Store::new(0);
+        first.reducer(|count: &mut i32, Message::Add(amount)| *count += amount);
+        register_stores(&mut TestStates::new().states);
+
+        let second = Store::new(0);
+        second.reducer(|count: &mut i32, Message::Add(amount)| *count -= amount);
+        let mut test = TestStates::new();
+        let mut context = test.context_mut();
+        context.dispatch(Message::Add(1)).unwrap();
+        assert_eq!(*context.get(second.binding()), -1);
+    }
+}
@@ -1,13 +1,18 @@
 use super::{
+    animation::Curve,
     constraints::Constraints,
     context::{Context, ContextMut},
+    error::{raise, ViewError},
     interaction::Interaction,
     layout::Layout,
     view_tree::{ViewDrawer, ViewInteractor, ViewSizer},
 };
 use crate::graphics::painter::Painter;
 use crate::math::Vec2;
-use std::{any::Any, rc::Rc};
+use std::{
+    any::{Any, TypeId},
+    rc::Rc,
+};
 
 #[allow(unused, private_bounds)]
 pub trait View: 'static + ViewEq {
@@ -28,6 +33,18 @@ pub trait View: 'static + ViewEq {
         children: &[ViewInteractor],
     ) -> bool;
 
+    fn layout_curve(&self) -> Option<Curve> {
+        None
+    }
+
+    fn key(&self) -> Option<u64> {
+        None
+    }
+
+    fn is_error_boundary(&self) -> bool {
+        false
+    }
+
     fn debug_name(&self) -> &str {
         let mut type_name = std::any::type_name::<Self>();
         if let Some(generic_start) = type_name.find("<") {
@@ -57,6 +74,75 @@ impl<T: 'static + PartialEq> ViewEq for T {
     }
 }
 
+// lets a component fail its build with `Err`, caught by the nearest error boundary
+impl<V: View + PartialEq> View for Result<V, ViewError> {
+    fn build(&self, context: &mut Context) -> Vec<Rc<dyn View>> {
+        match self {
+            Ok(view) => view.build(context),
+            Err(error) => raise(error.clone()),
+        }
+    }
+
+    fn size(&self, constraints: Constraints, children: &[ViewSizer]) -> Vec2 {
+        match self {
+            Ok(view) => view.size(constraints, children),
+            Err(_) => Vec2::ZERO,
+        }
+    }
+
+    fn layout(&self, layout: Layout, children: &[ViewSizer]) -> Vec<Layout> {
+        match self {
+            Ok(view) => view.layout(layout, children),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    fn draw(&self, layout: Layout, painter: &mut Painter, children: &[ViewDrawer]) {
+        if let Ok(view) = self {
+            view.draw(layout, painter, children);
+        }
+    }
+
+    fn interact(
+        &self,
+        context: &mut ContextMut,
+        layout: Layout,
+        interaction: Interaction,
+        consumed: bool,
+        children: &[ViewInteractor],
+    ) -> bool {
+        match self {
+            Ok(view) => view.interact(context, layout, interaction, consumed, children),
+            Err(_) => false,
+        }
+    }
+
+    fn layout_curve(&self) -> Option<Curve> {
+        self.as_ref().ok().and_then(View::layout_curve)
+    }
+
+    fn key(&self) -> Option<u64> {
+        self.as_ref().ok().and_then(View::key)
+    }
+
+    fn is_error_boundary(&self) -> bool {
+        self.as_ref().is_ok_and(View::is_error_boundary)
+    }
+
+    fn debug_name(&self) -> &str {
+        match self {
+            Ok(view) => view.debug_name(),
+            Err(_) => "Err",
+        }
+    }
+}
+
+impl dyn View {
+    pub(crate) fn view_type_id(&self) -> TypeId {
+        ViewEq::as_any(self).type_id()
+    }
+}
+
 impl PartialEq for dyn View {
     fn eq(&self, other: &Self) -> bool {
         ViewEq::eq(self, ViewEq::as_any(other))
@@ -1,6 +1,8 @@
 use super::{
+    animation::Curve,
     constraints::{Constraint, Constraints},
-    context::{Context, ContextMut},
+    context::{Context, ContextMut, Dependent, StateDependencies, States},
+    error::{report, ViewError},
     interaction::Interaction,
     layout::Layout,
     view::View,
@@ -10,12 +12,33 @@ use crate::{
     graphics::painter::Painter,
     utils::id_vec::{Id, IdVec},
 };
-use itertools::{EitherOrBoth::*, Itertools};
-use std::{any::Any, cell::RefCell, rc::Rc};
+use itertools::Itertools;
+use std::{
+    any::Any,
+    cell::RefCell,
+    collections::{HashMap, HashSet, VecDeque},
+    panic::{self, AssertUnwindSafe},
+    rc::Rc,
+    time::Instant,
+};
+
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct DumpOptions {
+    pub states: bool,
+    pub dependencies: bool,
+}
 
 pub(crate) struct ViewTree {
     root: Id,
     nodes: IdVec<RefCell<Node>>,
+    removed: HashSet<Id>,
+    relayout: HashSet<Id>,
+    built: HashSet<Id>,
+    inserted: Vec<Id>,
+    failures: RefCell<HashMap<Id, ViewError>>,
+    time: Instant,
+    pass: usize,
+    root_constraints: Constraints,
 }
 
 struct Node {
@@ -24,6 +47,27 @@ struct Node {
 
     pub view: Rc<dyn View>,
     pub layout: Layout,
+    pub laid_out: bool,
+    pub transition: Option<Transition>,
+    // sizes with the layout pass they were last asked for in
+    pub sizes: HashMap<Constraints, (Vec2, usize)>,
+}
+
+struct Transition {
+    from: Layout,
+    start: Instant,
+    curve: Curve,
+}
+
+impl Node {
+    fn frame(&self, time: Instant) -> Layout {
+        if let Some(transition) = &self.transition {
+            let progress = transition.curve.progress(transition.start, time);
+            transition.from.lerp(self.layout, progress)
+        } else {
+            self.layout
+        }
+    }
 }
 
 impl ViewTree {
@@ -37,51 +81,169 @@ impl ViewTree {
                 position: Default::default(),
                 size: Default::default(),
             },
+            laid_out: false,
+            transition: None,
+            sizes: HashMap::new(),
         }));
 
-        let mut tree = ViewTree { root, nodes };
-        tree.rebuild(context, size, root);
+        let mut tree = ViewTree {
+            root,
+            nodes,
+            removed: HashSet::new(),
+            relayout: HashSet::new(),
+            built: HashSet::new(),
+            inserted: Vec::new(),
+            failures: RefCell::new(HashMap::new()),
+            time: Instant::now(),
+            pass: 0,
+            root_constraints: Constraints {
+                width: Constraint::Fixed(size.x),
+                height: Constraint::Fixed(size.y),
+            },
+        };
+        tree.rebuild(context, root);
+        tree.resize(size);
         tree
     }
 
-    pub(crate) fn rebuild(&mut self, context: &mut Context, size: Vec2, mut id: Id) {
-        self.build(context, id);
+    // parents go first so a dirty child already built by its parent, or removed by it, is skipped
+    pub(crate) fn rebuild_dirty(&mut self, context: &mut Context, dirty: HashSet<Id>) {
+        // dependencies can still name views removed since they were read
+        let dirty = dirty
+            .into_iter()
+            .filter(|&id| self.nodes.try_get(id).is_some())
+            .sorted_by_key(|&id| self.ancestors(id).len())
+            .collect_vec();
 
-        let size = ViewSizer {
-            tree: self,
-            id: self.root,
+        self.built.clear();
+        for id in dirty {
+            if self.nodes.contains(id) && !self.built.contains(&id) {
+                self.rebuild(context, id);
+            }
         }
-        .size(Constraints {
-            width: Constraint::Fixed(size.x),
-            height: Constraint::Fixed(size.y),
-        });
+    }
 
-        self.layout(
-            self.root,
-            Layout {
-                position: Vec2::ZERO,
-                size,
-            },
-        );
+    pub(crate) fn rebuild(&mut self, context: &mut Context, id: Id) {
+        let ancestors = self.ancestors(id);
+        let boundary = std::iter::once(id)
+            .chain(ancestors.iter().copied())
+            .find(|&id| self.nodes[id].borrow().view.is_error_boundary());
+        let mut current = id;
+        let mut sizes = self.nodes[id].borrow().sizes.clone();
+        let boundary_sizes = boundary
+            .filter(|&boundary| boundary != id)
+            .map(|boundary| self.nodes[boundary].borrow().sizes.clone());
+
+        // a failure below `id` rebuilds its boundary, which takes the place of `id`
+        if !self.build_within(context, id, &ancestors, boundary) {
+            if let (Some(boundary), Some(boundary_sizes)) = (boundary, boundary_sizes) {
+                current = boundary;
+                sizes = boundary_sizes;
+            }
+        }
+        self.inserted.clear();
+
+        if !self.removed.is_empty() {
+            context.release(&self.removed);
+            self.removed.clear();
+        }
+
+        // measures every node up to the root again under the constraints of its last layout pass,
+        // sizes of earlier passes depended on the old subtree so they are dropped. Above the
+        // topmost node that sizes differently layouts can't change, so only the subtree of its
+        // parent has to be laid out again. A node that was never laid out can't be compared, so
+        // it counts as changed
+        let mut relayout = current;
+        loop {
+            let last = last_sizes(&sizes);
+            let changed = last.is_empty()
+                || last.iter().any(|&(constraints, size)| {
+                    ViewSizer {
+                        tree: self,
+                        id: current,
+                    }
+                    .size(constraints)
+                        != size
+                });
+            let parent = self.nodes[current].borrow().parent;
+            if changed {
+                relayout = parent.unwrap_or(current);
+            }
+            let Some(parent) = parent else {
+                break;
+            };
+            sizes = std::mem::take(&mut self.nodes[parent].borrow_mut().sizes);
+            current = parent;
+        }
+        self.relayout.insert(relayout);
     }
 
     pub(crate) fn resize(&mut self, size: Vec2) {
+        self.pass += 1;
+        self.root_constraints = Constraints {
+            width: Constraint::Fixed(size.x),
+            height: Constraint::Fixed(size.y),
+        };
         let size = ViewSizer {
             tree: self,
             id: self.root,
         }
-        .size(Constraints {
-            width: Constraint::Fixed(size.x),
-            height: Constraint::Fixed(size.y),
-        });
+        .size(self.root_constraints);
 
+        // resizing the window lays out in place, only rebuilds animate
         self.layout(
             self.root,
             Layout {
                 position: Vec2::ZERO,
                 size,
             },
+            false,
         );
+        self.relayout.clear();
+    }
+
+    pub(crate) fn relayout(&mut self) {
+        self.pass += 1;
+        for id in std::mem::take(&mut self.relayout) {
+            let Some(node) = self.nodes.try_get(id) else {
+                continue;
+            };
+            // the root has no parent to size it again, so it's measured under the window's constraints
+            let layout = if id == self.root {
+                Layout {
+                    position: Vec2::ZERO,
+                    size: ViewSizer { tree: self, id }.size(self.root_constraints),
+                }
+            } else {
+                node.borrow().layout
+            };
+            self.layout(id, layout, true);
+        }
+    }
+
+    pub(crate) fn tick(&mut self, time: Instant) -> bool {
+        self.time = time;
+
+        let mut animating = false;
+        let mut transitioning = Vec::new();
+        for (id, node) in self.nodes.iter() {
+            let mut node = node.borrow_mut();
+            if let Some(transition) = &node.transition {
+                if time >= transition.start + transition.curve.duration {
+                    node.transition = None;
+                } else {
+                    animating = true;
+                }
+                transitioning.push(id);
+            }
+        }
+
+        // children follow the interpolated frame, a finished transition lays them out at the final one
+        for id in transitioning {
+            let frame = self.nodes[id].borrow().frame(time);
+            self.guard(id, || self.layout_children(id, frame, false));
+        }
+        animating
     }
 
     pub(crate) fn draw(&self, painter: &mut Painter) {
@@ -100,10 +262,143 @@ impl ViewTree {
         .interact(context, interaction, false)
     }
 
-    fn build(&mut self, context: &mut Context, id: Id) {
+    pub(crate) fn dump(
+        &self,
+        options: DumpOptions,
+        states: &States,
+        dependencies: &StateDependencies,
+    ) -> String {
+        let mut output = String::new();
+        self.dump_node(&mut output, self.root, 0, options, states, dependencies);
+        output
+    }
+
+    fn dump_node(
+        &self,
+        output: &mut String,
+        id: Id,
+        depth: usize,
+        options: DumpOptions,
+        states: &States,
+        dependencies: &StateDependencies,
+    ) {
         let node = self.nodes[id].borrow();
+        let indent = "  ".repeat(depth);
+        let Layout { position, size } = node.layout;
+        output.push_str(&format!(
+            "{indent}{} {id} ({}, {}) {}x{}\n",
+            node.view.debug_name(),
+            position.x,
+            position.y,
+            size.x,
+            size.y
+        ));
+
+        if options.states {
+            for (_, type_id, slot) in states
+                .keys()
+                .filter(|(owner, _, _)| *owner == Some(id))
+                .sorted_by_key(|key| format!("{:?}", key))
+            {
+                output.push_str(&format!("{indent}  owns {slot:?} {type_id:?}\n"));
+            }
+        }
+        if options.dependencies {
+            for (owner, type_id, slot) in dependencies
+                .get_u_connections(Dependent::View(Some(id)))
+                .iter()
+                .sorted_by_key(|key| format!("{:?}", key))
+            {
+                let owner = owner.map_or("app".to_string(), |owner| owner.to_string());
+                output.push_str(&format!("{indent}  reads {owner} {slot:?} {type_id:?}\n"));
+            }
+        }
+
+        for &child_id in node.children.iter() {
+            self.dump_node(output, child_id, depth + 1, options, states, dependencies);
+        }
+    }
+
+    // a boundary can be removed by a rebuild after it failed
+    pub(crate) fn take_failures(&self) -> HashMap<Id, ViewError> {
+        std::mem::take(&mut *self.failures.borrow_mut())
+            .into_iter()
+            .filter(|&(id, _)| self.nodes.try_get(id).is_some())
+            .collect()
+    }
 
-        let children = context.with_id(id, |context| node.view.build(context));
+    fn build(&mut self, context: &mut Context, id: Id, ancestors: &[Id]) {
+        let boundary = self.nodes[id]
+            .borrow()
+            .view
+            .is_error_boundary()
+            .then_some(id);
+        self.build_within(context, id, ancestors, boundary);
+    }
+
+    // on a panic the boundary drops its whole subtree, including nodes inserted before the panic
+    // that were never attached to a parent, and builds again showing its fallback
+    // `ancestors` are the ancestors of `id`, nearest first, and contain the boundary unless it's `id`
+    fn build_within(
+        &mut self,
+        context: &mut Context,
+        id: Id,
+        ancestors: &[Id],
+        boundary: Option<Id>,
+    ) -> bool {
+        let Some(boundary) = boundary else {
+            self.build_node(context, id, ancestors);
+            return true;
+        };
+
+        let inserted = self.inserted.len();
+        let result =
+            panic::catch_unwind(AssertUnwindSafe(|| self.build_node(context, id, ancestors)));
+        let Err(payload) = result else {
+            return true;
+        };
+
+        let error = ViewError::from_panic(payload);
+        for orphan in self.inserted.split_off(inserted) {
+            self.remove(orphan);
+        }
+        let children = std::mem::take(&mut self.nodes[boundary].borrow_mut().children);
+        for &child_id in children.iter() {
+            self.remove(child_id);
+        }
+
+        report(&error);
+        context.fail_boundary(boundary, error);
+        let boundary_ancestors = match ancestors.iter().position(|&id| id == boundary) {
+            Some(index) => &ancestors[index + 1..],
+            None => ancestors,
+        };
+        self.build_node(context, boundary, boundary_ancestors);
+        false
+    }
+
+    fn fail(&self, id: Id, payload: Box<dyn Any + Send>) {
+        self.failures
+            .borrow_mut()
+            .entry(id)
+            .or_insert_with(|| ViewError::from_panic(payload));
+    }
+
+    fn guard<R>(&self, id: Id, f: impl FnOnce() -> R) -> Option<R> {
+        if !self.nodes[id].borrow().view.is_error_boundary() {
+            return Some(f());
+        }
+        panic::catch_unwind(AssertUnwindSafe(f))
+            .map_err(|payload| self.fail(id, payload))
+            .ok()
+    }
+
+    fn build_node(&mut self, context: &mut Context, id: Id, ancestors: &[Id]) {
+        self.built.insert(id);
+        self.nodes[id].borrow_mut().sizes.clear();
+        let node = self.nodes[id].borrow();
+
+        let children = context.with_id(id, ancestors, |context| node.view.build(context));
         let (paired_children, unused_children) =
             self.pair_children(children.into_iter(), node.children.iter().copied());
 
@@ -113,6 +408,9 @@ impl ViewTree {
             self.remove(id);
         }
 
+        let child_ancestors = std::iter::once(id)
+            .chain(ancestors.iter().copied())
+            .collect_vec();
         self.nodes[id].borrow_mut().children = paired_children
             .into_iter()
             .map(|(child_view, child_id)| {
@@ -126,29 +424,41 @@ impl ViewTree {
                         if *child_node.view != *child_view {
                             child_node.view = child_view;
                             drop(child_node);
-                            self.build(context, child_id)
+                            self.build(context, child_id, &child_ancestors)
                         }
                     }
                     child_id
                 } else {
                     let child_id = self.insert(id, child_view);
-                    self.build(context, child_id);
+                    self.build(context, child_id, &child_ancestors);
                     child_id
                 }
             })
             .collect();
     }
 
+    fn ancestors(&self, id: Id) -> Vec<Id> {
+        let mut ancestors = Vec::new();
+        let mut parent = self.nodes[id].borrow().parent;
+        while let Some(id) = parent {
+            ancestors.push(id);
+            parent = self.nodes[id].borrow().parent;
+        }
+        ancestors
+    }
+
     fn remove(&mut self, id: Id) {
-        let children = self.nodes[id].borrow().children.clone();
-        for child_id in children {
+        let Some(node) = self.nodes.remove(id) else {
+            return;
+        };
+        for &child_id in node.into_inner().children.iter() {
             self.remove(child_id);
         }
-        self.nodes.remove(id);
+        self.removed.insert(id);
     }
 
     fn insert(&mut self, parent: Id, view: Rc<dyn View>) -> Id {
-        self.nodes.insert(RefCell::new(Node {
+        let id = self.nodes.insert(RefCell::new(Node {
             parent: Some(parent),
             children: Default::default(),
             view,
@@ -156,7 +466,12 @@ impl ViewTree {
                 position: Default::default(),
                 size: Default::default(),
             },
-        }))
+            laid_out: false,
+            transition: None,
+            sizes: HashMap::new(),
+        }));
+        self.inserted.push(id);
+        id
     }
 
     fn pair_children(
@@ -164,31 +479,67 @@ impl ViewTree {
         children: impl Iterator<Item = Rc<dyn View>>,
         child_indices: impl Iterator<Item = Id>,
     ) -> (Vec<(Rc<dyn View>, Option<Id>)>, Vec<Id>) {
-        let mut paired_children = Vec::new();
+        // keyed children are matched by key wherever they moved, the rest by their order
+        let mut keyed_ids = HashMap::new();
+        let mut unkeyed_ids = VecDeque::new();
         let mut unused_children = Vec::new();
-        for pair in children.zip_longest(child_indices) {
-            match pair {
-                Both(child, id) => {
-                    let old_child = self.nodes[id].borrow();
-                    if old_child.view.type_id() == child.type_id() {
-                        paired_children.push((child, Some(id)));
-                    } else {
-                        unused_children.push(id);
-                        paired_children.push((child, None));
-                    }
+        for id in child_indices {
+            if let Some(key) = self.nodes[id].borrow().view.key() {
+                unused_children.extend(keyed_ids.insert(key, id));
+            } else {
+                unkeyed_ids.push_back(id);
+            }
+        }
+
+        let mut paired_children = Vec::new();
+        for child in children {
+            let id = match child.key() {
+                Some(key) => keyed_ids.remove(&key),
+                None => unkeyed_ids.pop_front(),
+            };
+            match id {
+                Some(id) if self.nodes[id].borrow().view.view_type_id() == child.view_type_id() => {
+                    paired_children.push((child, Some(id)));
                 }
-                Left(child) => paired_children.push((child, None)),
-                Right(index) => unused_children.push(index),
+                Some(id) => {
+                    unused_children.push(id);
+                    paired_children.push((child, None));
+                }
+                None => paired_children.push((child, None)),
             }
         }
+        unused_children.extend(keyed_ids.into_values());
+        unused_children.extend(unkeyed_ids);
         (paired_children, unused_children)
     }
 
-    fn layout(&self, id: Id, layout: Layout) {
+    fn layout(&self, id: Id, layout: Layout, animate: bool) {
+        self.guard(id, || self.layout_node(id, layout, animate));
+    }
+
+    fn layout_node(&self, id: Id, layout: Layout, animate: bool) {
         let mut node = self.nodes[id].borrow_mut();
+        if let Some(curve) = node.view.layout_curve() {
+            if animate && node.laid_out && node.layout != layout {
+                let time = Instant::now();
+                node.transition = Some(Transition {
+                    from: node.frame(time),
+                    start: time,
+                    curve,
+                });
+            }
+        }
         node.layout = layout;
+        node.laid_out = true;
+        let frame = node.frame(self.time);
+        drop(node);
+        self.layout_children(id, frame, animate);
+    }
+
+    fn layout_children(&self, id: Id, frame: Layout, animate: bool) {
+        let node = self.nodes[id].borrow();
         let layouts = node.view.layout(
-            layout,
+            frame,
             &node
                 .children
                 .iter()
@@ -200,11 +551,20 @@ impl ViewTree {
         );
 
         for (&child_id, layout) in node.children.iter().zip(layouts) {
-            self.layout(child_id, layout);
+            self.layout(child_id, layout, animate);
         }
     }
 }
 
+fn last_sizes(sizes: &HashMap<Constraints, (Vec2, usize)>) -> Vec<(Constraints, Vec2)> {
+    let last = sizes.values().map(|&(_, measured)| measured).max();
+    sizes
+        .iter()
+        .filter(|(_, &(_, measured))| Some(measured) == last)
+        .map(|(&constraints, &(size, _))| (constraints, size))
+        .collect()
+}
+
 pub struct ViewSizer<'a> {
     tree: &'a ViewTree,
     id: Id,
@@ -212,18 +572,50 @@ pub struct ViewSizer<'a> {
 
 impl ViewSizer<'_> {
     pub fn size(&self, constraints: Constraints) -> Vec2 {
+        let pass = self.tree.pass;
+        if let Some((size, measured)) = self.tree.nodes[self.id]
+            .borrow_mut()
+            .sizes
+            .get_mut(&constraints)
+        {
+            *measured = pass;
+            return *size;
+        }
+
         let node = self.tree.nodes[self.id].borrow();
-        node.view.size(
-            constraints,
-            &node
-                .children
-                .iter()
-                .map(|&id| ViewSizer {
-                    tree: self.tree,
-                    id,
-                })
-                .collect_vec(),
-        )
+        let size = self.tree.guard(self.id, || {
+            node.view.size(
+                constraints,
+                &node
+                    .children
+                    .iter()
+                    .map(|&id| ViewSizer {
+                        tree: self.tree,
+                        id,
+                    })
+                    .collect_vec(),
+            )
+        });
+        drop(node);
+
+        // a failed size isn't cached, the boundary rebuilds with its fallback anyway
+        let Some(size) = size else {
+            return Vec2::ZERO;
+        };
+
+        // besides this pass only the sizes of the last pass the node was laid out in are kept,
+        // so resizing the window over and over doesn't grow the cache
+        let mut node = self.tree.nodes[self.id].borrow_mut();
+        let last = node
+            .sizes
+            .values()
+            .map(|&(_, measured)| measured)
+            .filter(|&measured| measured < pass)
+            .max();
+        node.sizes
+            .retain(|_, &mut (_, measured)| measured == pass || Some(measured) == last);
+        node.sizes.insert(constraints, (size, pass));
+        size
     }
 }
 
@@ -234,9 +626,19 @@ pub struct ViewDrawer<'a> {
 
 impl ViewDrawer<'_> {
     pub fn draw(&self, painter: &mut Painter) {
+        if self.tree.nodes[self.id].borrow().view.is_error_boundary() {
+            if let Err(payload) = painter.catch_unwind(|painter| self.draw_node(painter)) {
+                self.tree.fail(self.id, payload);
+            }
+        } else {
+            self.draw_node(painter);
+        }
+    }
+
+    fn draw_node(&self, painter: &mut Painter) {
         let node = self.tree.nodes[self.id].borrow();
         node.view.draw(
-            node.layout,
+            node.frame(self.tree.time),
             painter,
             &node
                 .children
@@ -266,7 +668,7 @@ impl ViewInteractor<'_> {
             let node = self.tree.nodes[self.id].borrow();
             node.view.interact(
                 context,
-                node.layout,
+                node.frame(self.tree.time),
                 interaction,
                 consumed,
                 &node
@@ -281,3 +683,563 @@ impl ViewInteractor<'_> {
         })
     }
 }
+
+mod tests {
+    use super::*;
+    use crate::{
+        core::{
+            app::App,
+            context::{tests::TestStates, Binding, StateKey, StateSlot},
+            error::{boundary_key, BoundaryError},
+            interaction::Lifecycle,
+        },
+        views::{
+            animated_layout::LayoutAnimatable, component::Component, environment::EnvironmentExt,
+            error_boundary::ErrorBoundaryExt, flex::col, lifecycle::LifecycleListenerExt,
+            spacer::spacer, ContentBuilder,
+        },
+    };
+    use std::{any::TypeId, cell::Cell, time::Duration};
+
+    #[derive(PartialEq)]
+    struct List;
+
+    impl Component for List {
+        fn build(&self, ctx: &mut Context) -> impl View {
+            let items = ctx.state(|| vec![1u32, 2, 3]);
+            col(ContentBuilder::from_keyed_items(
+                ctx.get(items).clone().into_iter(),
+                |&item| item,
+                Item,
+            ))
+        }
+    }
+
+    #[derive(PartialEq)]
+    struct Item(u32);
+
+    impl Component for Item {
+        fn build(&self, ctx: &mut Context) -> impl View {
+            let Self(value) = *self;
+            ctx.state(move || value);
+            col(ContentBuilder::from_vec(Vec::new()))
+        }
+    }
+
+    #[test]
+    fn keyed_children_keep_state() {
+        let mut test = TestStates::new();
+        let mut context = test.context();
+        let mut tree = ViewTree::build_from(&mut context, Vec2::new(100.0, 100.0), List);
+
+        let items = Binding::<Vec<u32>>::new(Some(tree.root), StateSlot::Index(0));
+        test.poke(items, vec![3, 1]);
+        let mut context = test.context();
+        tree.rebuild(&mut context, tree.root);
+
+        let mut values = Vec::new();
+        for (id, node) in tree.nodes.iter() {
+            let key = (Some(id), TypeId::of::<u32>(), StateSlot::Index(0));
+            if let Some(&value) = test.states.get(&key).and_then(|state| state.downcast_ref()) {
+                assert!(*node.borrow().view == *(Rc::new(Item(value)) as Rc<dyn View>));
+                values.push(value);
+            }
+        }
+        values.sort();
+        assert_eq!(values, vec![1, 3]);
+    }
+
+    #[derive(PartialEq)]
+    struct Counted(Rc<Cell<usize>>);
+
+    impl View for Counted {
+        fn build(&self, context: &mut Context) -> Vec<Rc<dyn View>> {
+            Vec::new()
+        }
+
+        fn size(&self, constraints: Constraints, children: &[ViewSizer]) -> Vec2 {
+            self.0.set(self.0.get() + 1);
+            Vec2::new(10.0, 10.0)
+        }
+
+        fn layout(&self, layout: Layout, children: &[ViewSizer]) -> Vec<Layout> {
+            Vec::new()
+        }
+
+        fn draw(&self, layout: Layout, painter: &mut Painter, children: &[ViewDrawer]) {}
+
+        fn interact(
+            &self,
+            context: &mut ContextMut,
+            layout: Layout,
+            interaction: Interaction,
+            consumed: bool,
+            children: &[ViewInteractor],
+        ) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn sizes_cached_until_rebuilt() {
+        let mut test = TestStates::new();
+        let mut context = test.context();
+
+        let count = Rc::new(Cell::new(0));
+        let mut tree = ViewTree::build_from(
+            &mut context,
+            Vec2::new(100.0, 100.0),
+            Counted(count.clone()),
+        );
+        assert_eq!(count.get(), 1);
+
+        tree.resize(Vec2::new(100.0, 100.0));
+        assert_eq!(count.get(), 1);
+        tree.resize(Vec2::new(200.0, 100.0));
+        assert_eq!(count.get(), 2);
+        tree.resize(Vec2::new(300.0, 100.0));
+        assert_eq!(count.get(), 3);
+        assert_eq!(tree.nodes[tree.root].borrow().sizes.len(), 2);
+        tree.resize(Vec2::new(200.0, 100.0));
+        assert_eq!(count.get(), 3);
+
+        // rebuilding measures again only under the constraints of the last layout
+        tree.rebuild(&mut context, tree.root);
+        assert_eq!(count.get(), 4);
+        tree.resize(Vec2::new(200.0, 100.0));
+        assert_eq!(count.get(), 4);
+    }
+
+    // wraps to the given height when narrower than 250
+    #[derive(PartialEq)]
+    struct Wrapped(f32);
+
+    impl View for Wrapped {
+        fn build(&self, context: &mut Context) -> Vec<Rc<dyn View>> {
+            Vec::new()
+        }
+
+        fn size(&self, constraints: Constraints, children: &[ViewSizer]) -> Vec2 {
+            let Constraint::Fixed(width) = constraints.width else {
+                return Vec2::ZERO;
+            };
+            Vec2::new(width, if width < 250.0 { self.0 } else { 10.0 })
+        }
+
+        fn layout(&self, layout: Layout, children: &[ViewSizer]) -> Vec<Layout> {
+            Vec::new()
+        }
+
+        fn draw(&self, layout: Layout, painter: &mut Painter, children: &[ViewDrawer]) {}
+
+        fn interact(
+            &self,
+            context: &mut ContextMut,
+            layout: Layout,
+            interaction: Interaction,
+            consumed: bool,
+            children: &[ViewInteractor],
+        ) -> bool {
+            false
+        }
+    }
+
+    #[derive(PartialEq)]
+    struct Paragraph;
+
+    impl Component for Paragraph {
+        fn build(&self, ctx: &mut Context) -> impl View {
+            let height = ctx.state(|| 20.0f32);
+            Wrapped(*ctx.get(height))
+        }
+    }
+
+    #[test]
+    fn rebuilds_drop_sizes_of_earlier_passes() {
+        let mut test = TestStates::new();
+        let mut context = test.context();
+        let mut tree = ViewTree::build_from(
+            &mut context,
+            Vec2::new(200.0, 100.0),
+            crate::col![crate::col![Paragraph], spacer()],
+        );
+        tree.resize(Vec2::new(300.0, 100.0));
+
+        // only the size narrower than the last layout changes, so the climb stops right away
+        let column = tree.nodes[tree.root].borrow().children[0];
+        let paragraph = tree.nodes[column].borrow().children[0];
+        test.poke(Binding::new(Some(paragraph), StateSlot::Index(0)), 40.0f32);
+        let mut context = test.context();
+        tree.rebuild(&mut context, paragraph);
+        assert_eq!(tree.relayout, HashSet::from([paragraph]));
+        tree.relayout();
+
+        tree.resize(Vec2::new(200.0, 100.0));
+        let layout = |id: Id| tree.nodes[id].borrow().layout;
+        let spacer = tree.nodes[tree.root].borrow().children[1];
+        assert_eq!(layout(column).size, Vec2::new(200.0, 40.0));
+        assert_eq!(layout(spacer).position, Vec2::new(0.0, 40.0));
+    }
+
+    #[derive(PartialEq)]
+    struct Panel;
+
+    impl Component for Panel {
+        fn build(&self, ctx: &mut Context) -> impl View {
+            crate::col![Row, spacer()]
+        }
+    }
+
+    #[derive(PartialEq)]
+    struct Row;
+
+    impl Component for Row {
+        fn build(&self, ctx: &mut Context) -> impl View {
+            let height = ctx.state(|| 10.0f32);
+            spacer().height(*ctx.get(height))
+        }
+    }
+
+    #[test]
+    fn relayout_stops_at_unchanged_size() {
+        let mut test = TestStates::new();
+        let mut context = test.context();
+        let mut tree = ViewTree::build_from(&mut context, Vec2::new(100.0, 100.0), Panel);
+
+        let row = tree
+            .nodes
+            .iter()
+            .find(|(_, node)| *node.borrow().view == *(Rc::new(Row) as Rc<dyn View>))
+            .map(|(id, _)| id)
+            .unwrap();
+
+        tree.rebuild(&mut context, row);
+        assert_eq!(tree.relayout, HashSet::from([row]));
+        tree.relayout();
+
+        let height = Binding::<f32>::new(Some(row), StateSlot::Index(0));
+        test.poke(height, 20.0);
+        let mut context = test.context();
+        tree.rebuild(&mut context, row);
+        let flex = tree.nodes[row].borrow().parent.unwrap();
+        assert_eq!(tree.relayout, HashSet::from([flex]));
+
+        tree.relayout();
+        let layout = |id: Id| tree.nodes[id].borrow().layout;
+        let spacer = tree.nodes[flex].borrow().children[1];
+        assert_eq!(layout(flex).size, Vec2::new(100.0, 100.0));
+        assert_eq!(layout(row).size, Vec2::new(100.0, 20.0));
+        assert_eq!(layout(spacer).position, Vec2::new(0.0, 20.0));
+        assert_eq!(layout(spacer).size, Vec2::new(100.0, 80.0));
+    }
+
+    #[test]
+    fn changes_laid_out_once() {
+        let mut test = TestStates::new();
+        let mut context = test.context();
+        let mut tree =
+            ViewTree::build_from(&mut context, Vec2::new(100.0, 100.0), crate::col![Row, Row]);
+
+        let rows = tree.nodes[tree.root].borrow().children.clone();
+        let heights = rows
+            .iter()
+            .map(|&row| Binding::<f32>::new(Some(row), StateSlot::Index(0)))
+            .collect_vec();
+        for &height in &heights {
+            test.poke(height, 20.0);
+        }
+        settle(
+            &mut test,
+            &mut tree,
+            heights.iter().map(|&height| height.into()).collect(),
+        );
+
+        // both rows are built before anything is laid out again
+        let layout = |tree: &ViewTree, id: Id| tree.nodes[id].borrow().layout;
+        assert_eq!(layout(&tree, rows[1]).position, Vec2::new(0.0, 10.0));
+        assert_eq!(tree.relayout, HashSet::from([tree.root]));
+
+        tree.relayout();
+        assert_eq!(layout(&tree, rows[0]).size, Vec2::new(100.0, 20.0));
+        assert_eq!(layout(&tree, rows[1]).position, Vec2::new(0.0, 20.0));
+    }
+
+    #[test]
+    fn dirty_views_rebuilt_top_down() {
+        let mut test = TestStates::new();
+        let mut context = test.context();
+        let mut tree = ViewTree::build_from(&mut context, Vec2::new(100.0, 100.0), List);
+
+        let item = |tree: &ViewTree, value: u32| {
+            tree.nodes
+                .iter()
+                .find(|(_, node)| *node.borrow().view == *(Rc::new(Item(value)) as Rc<dyn View>))
+                .map(|(id, _)| id)
+                .unwrap()
+        };
+        let removed = item(&tree, 2);
+        let kept = item(&tree, 3);
+
+        let items = Binding::<Vec<u32>>::new(Some(tree.root), StateSlot::Index(0));
+        test.poke(items, vec![1, 3]);
+        let mut context = test.context();
+        tree.rebuild_dirty(&mut context, HashSet::from([kept, removed, tree.root]));
+
+        assert!(!tree.nodes.contains(removed));
+        assert!(tree.built.contains(&kept));
+    }
+
+    // invalidates and rebuilds until no more states change, like a frame of the app
+    fn settle(test: &mut TestStates, tree: &mut ViewTree, mut changed: Vec<StateKey>) {
+        while !changed.is_empty() {
+            let mut context = test.context();
+            let dirty = context.invalidate(changed);
+            tree.rebuild_dirty(&mut context, dirty);
+            changed = test.changes.drain().collect_vec();
+        }
+    }
+
+    #[derive(PartialEq)]
+    struct Reader(Rc<Cell<usize>>);
+
+    impl Component for Reader {
+        fn build(&self, ctx: &mut Context) -> impl View {
+            self.0.set(self.0.get() + 1);
+            spacer().height(*ctx.env::<f32>())
+        }
+    }
+
+    #[derive(PartialEq)]
+    struct Plain(Rc<Cell<usize>>);
+
+    impl Component for Plain {
+        fn build(&self, ctx: &mut Context) -> impl View {
+            self.0.set(self.0.get() + 1);
+            spacer()
+        }
+    }
+
+    #[derive(PartialEq)]
+    struct Themed(Rc<Cell<usize>>, Rc<Cell<usize>>);
+
+    impl Component for Themed {
+        fn build(&self, ctx: &mut Context) -> impl View {
+            let height = ctx.state(|| 10.0f32);
+            crate::col![Reader(self.0.clone()), Plain(self.1.clone())].environment(*ctx.get(height))
+        }
+    }
+
+    #[test]
+    fn environment_from_nearest_ancestor() {
+        let mut test = TestStates::new();
+        let mut context = test.context();
+        let builds = Rc::new(Cell::new(0));
+        let tree = ViewTree::build_from(
+            &mut context,
+            Vec2::new(100.0, 100.0),
+            crate::col![
+                Reader(builds.clone()),
+                Reader(builds.clone()).environment(20.0f32)
+            ]
+            .environment(10.0f32),
+        );
+
+        let column = tree.nodes[tree.root].borrow().children[0];
+        let children = tree.nodes[column].borrow().children.clone();
+        let layout = |id: Id| tree.nodes[id].borrow().layout;
+        assert_eq!(layout(children[0]).size, Vec2::new(100.0, 10.0));
+        assert_eq!(layout(children[1]).size, Vec2::new(100.0, 20.0));
+    }
+
+    #[test]
+    fn environment_changes_rebuild_readers() {
+        let mut test = TestStates::new();
+        let mut context = test.context();
+        let (reads, plain) = (Rc::new(Cell::new(0)), Rc::new(Cell::new(0)));
+        let mut tree = ViewTree::build_from(
+            &mut context,
+            Vec2::new(100.0, 100.0),
+            Themed(reads.clone(), plain.clone()),
+        );
+        assert_eq!((reads.get(), plain.get()), (1, 1));
+
+        let height = Binding::<f32>::new(Some(tree.root), StateSlot::Index(0));
+        settle(&mut test, &mut tree, vec![height.into()]);
+        assert_eq!((reads.get(), plain.get()), (1, 1));
+
+        test.poke(height, 20.0);
+        settle(&mut test, &mut tree, vec![height.into()]);
+        assert_eq!((reads.get(), plain.get()), (2, 1));
+    }
+
+    #[derive(PartialEq)]
+    struct Broken;
+
+    impl Component for Broken {
+        fn build(&self, ctx: &mut Context) -> impl View {
+            let missing = Binding::<f32>::new(None, StateSlot::Key("missing"));
+            spacer().height(*ctx.get(missing))
+        }
+    }
+
+    #[test]
+    fn error_boundary_shows_fallback() {
+        let mut test = TestStates::new();
+        let mut context = test.context();
+        let tree = ViewTree::build_from(
+            &mut context,
+            Vec2::new(100.0, 100.0),
+            crate::col![Broken.error_boundary(|_, _| spacer()), Row],
+        );
+
+        let (boundary, node) = tree
+            .nodes
+            .iter()
+            .find(|(_, node)| node.borrow().view.is_error_boundary())
+            .unwrap();
+        let fallback = node.borrow().children[0];
+        assert!(
+            tree.nodes[fallback].borrow().view.view_type_id()
+                == (Rc::new(spacer()) as Rc<dyn View>).view_type_id()
+        );
+        assert!(tree
+            .nodes
+            .iter()
+            .any(|(_, node)| *node.borrow().view == *(Rc::new(Row) as Rc<dyn View>)));
+
+        let error = test
+            .states
+            .get(&boundary_key(Some(boundary)))
+            .and_then(|state| state.downcast_ref::<BoundaryError>())
+            .map(|BoundaryError(error)| error.clone());
+        assert_eq!(error, Some(ViewError::MissingState("f32")));
+    }
+
+    #[test]
+    fn error_boundary_retries() {
+        let mut test = TestStates::new();
+        let mut context = test.context();
+        let mut tree = ViewTree::build_from(
+            &mut context,
+            Vec2::new(100.0, 100.0),
+            Broken.error_boundary(|_, _| spacer()),
+        );
+
+        let missing = Binding::<f32>::new(None, StateSlot::Key("missing"));
+        test.states.insert(missing.into(), Box::new(10.0f32));
+        test.context_mut().clear_boundary(tree.root);
+
+        let changed = test.changes.drain().collect_vec();
+        let mut context = test.context();
+        let dirty = context.invalidate(changed);
+        tree.rebuild_dirty(&mut context, dirty);
+
+        let child = tree.nodes[tree.root].borrow().children[0];
+        assert!(*tree.nodes[child].borrow().view == *(Rc::new(Broken) as Rc<dyn View>));
+    }
+
+    #[test]
+    fn dump_lists_nodes_with_layouts() {
+        let mut test = TestStates::new();
+        let mut context = test.context();
+        let tree = ViewTree::build_from(&mut context, Vec2::new(100.0, 100.0), Panel);
+
+        let options = DumpOptions {
+            states: true,
+            dependencies: true,
+        };
+        let dump = tree.dump(options, &test.states, &test.dependencies);
+        let lines = dump.lines().collect_vec();
+        assert_eq!(lines[0], "Panel 0v0 (0, 0) 100x100");
+        assert_eq!(lines[2], "    Row 2v0 (0, 0) 100x10");
+        assert!(lines.contains(&"    Spacer 4v0 (0, 10) 100x90"));
+        assert!(lines
+            .iter()
+            .any(|line| line.starts_with("      owns Index(0)")));
+        assert!(lines
+            .iter()
+            .any(|line| line.starts_with("      reads 2v0 Index(0)")));
+
+        let dump = tree.dump(DumpOptions::default(), &test.states, &test.dependencies);
+        assert_eq!(dump.lines().count(), 5);
+        assert_eq!(
+            App::dump_tree(Panel, Vec2::new(100.0, 100.0), DumpOptions::default()),
+            dump
+        );
+    }
+
+    #[test]
+    fn close_requests_vetoed_by_listeners() {
+        let vetoed = |veto: bool, interaction: Interaction| {
+            let mut test = TestStates::new();
+            let mut context = test.context();
+            let tree = ViewTree::build_from(
+                &mut context,
+                Vec2::new(100.0, 100.0),
+                crate::col![spacer().on_close_requested(move |_| veto), spacer()],
+            );
+            tree.interact(&mut test.context_mut(), interaction)
+        };
+        let close_requested = Interaction::Lifecycle(Lifecycle::CloseRequested);
+
+        assert!(vetoed(true, close_requested));
+        assert!(!vetoed(false, close_requested));
+        assert!(!vetoed(
+            true,
+            Interaction::Lifecycle(Lifecycle::Focused(true))
+        ));
+    }
+
+    #[derive(PartialEq)]
+    struct Growing;
+
+    impl Component for Growing {
+        fn build(&self, ctx: &mut Context) -> impl View {
+            let width = ctx.state(|| 10.0f32);
+            spacer()
+                .width(*ctx.get(width))
+                .height(10.0)
+                .animate_layout(Curve::linear(Duration::from_secs(1)))
+        }
+    }
+
+    #[test]
+    fn transitions_interpolate_size() {
+        let mut test = TestStates::new();
+        let mut context = test.context();
+        let mut tree = ViewTree::build_from(
+            &mut context,
+            Vec2::new(100.0, 100.0),
+            crate::col![Growing, spacer()],
+        );
+
+        let growing = tree.nodes[tree.root].borrow().children[0];
+        let animated = tree.nodes[growing].borrow().children[0];
+        let spacer = tree.nodes[animated].borrow().children[0];
+        tree.resize(Vec2::new(200.0, 100.0));
+        assert!(tree.nodes[animated].borrow().transition.is_none());
+
+        let width = Binding::<f32>::new(Some(growing), StateSlot::Index(0));
+        test.poke(width, 20.0);
+        let mut context = test.context();
+        tree.rebuild(&mut context, growing);
+        tree.relayout();
+
+        let start = tree.nodes[animated]
+            .borrow()
+            .transition
+            .as_ref()
+            .unwrap()
+            .start;
+        tree.tick(start + Duration::from_millis(500));
+        assert_eq!(
+            tree.nodes[spacer].borrow().layout.size,
+            Vec2::new(15.0, 10.0)
+        );
+        assert!(!tree.tick(start + Duration::from_secs(1)));
+        assert_eq!(
+            tree.nodes[spacer].borrow().layout.size,
+            Vec2::new(20.0, 10.0)
+        );
+    }
+}
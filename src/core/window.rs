@@ -0,0 +1,181 @@
+use super::view_tree::DumpOptions;
+use crate::math::Vec2;
+use winit::window::Theme;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ColorScheme {
+    Light,
+    Dark,
+}
+
+impl From<Theme> for ColorScheme {
+    fn from(theme: Theme) -> Self {
+        match theme {
+            Theme::Light => ColorScheme::Light,
+            Theme::Dark => ColorScheme::Dark,
+        }
+    }
+}
+
+impl From<ColorScheme> for Theme {
+    fn from(color_scheme: ColorScheme) -> Self {
+        match color_scheme {
+            ColorScheme::Light => Theme::Light,
+            ColorScheme::Dark => Theme::Dark,
+        }
+    }
+}
+
+// The color scheme is stored under its own type so that an app level `ColorScheme` state
+// doesn't overwrite it.
+pub(crate) struct SystemColorScheme(pub(crate) ColorScheme);
+
+pub struct WindowHandle {
+    commands: Vec<WindowCommand>,
+    dump: Option<DumpRequest>,
+    tree_dump: Option<String>,
+    close: bool,
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct DumpRequest {
+    pub(crate) options: DumpOptions,
+    pub(crate) print: bool,
+}
+
+pub(crate) enum WindowCommand {
+    Title(String),
+    Size(Vec2),
+    MinSize(Option<Vec2>),
+    MaxSize(Option<Vec2>),
+    Position(Vec2),
+    Resizable(bool),
+    Fullscreen(bool),
+    Maximized(bool),
+    Minimized(bool),
+    AlwaysOnTop(bool),
+}
+
+impl WindowHandle {
+    pub(crate) fn new() -> Self {
+        Self {
+            commands: Vec::new(),
+            dump: None,
+            tree_dump: None,
+            close: false,
+        }
+    }
+
+    pub(crate) fn dump_requested(&self) -> bool {
+        self.dump.is_some()
+    }
+
+    pub(crate) fn take_dump(&mut self) -> Option<DumpRequest> {
+        self.dump.take()
+    }
+
+    pub(crate) fn set_tree_dump(&mut self, dump: String) {
+        self.tree_dump = Some(dump);
+    }
+
+    pub(crate) fn take_close(&mut self) -> bool {
+        std::mem::take(&mut self.close)
+    }
+
+    pub(crate) fn take_commands(&mut self) -> Vec<WindowCommand> {
+        std::mem::take(&mut self.commands)
+    }
+
+    pub fn set_title(&mut self, title: impl Into<String>) {
+        self.commands.push(WindowCommand::Title(title.into()));
+    }
+
+    pub fn set_size(&mut self, size: Vec2) {
+        self.commands.push(WindowCommand::Size(size));
+    }
+
+    pub fn set_min_size(&mut self, min_size: Option<Vec2>) {
+        self.commands.push(WindowCommand::MinSize(min_size));
+    }
+
+    pub fn set_max_size(&mut self, max_size: Option<Vec2>) {
+        self.commands.push(WindowCommand::MaxSize(max_size));
+    }
+
+    pub fn set_position(&mut self, position: Vec2) {
+        self.commands.push(WindowCommand::Position(position));
+    }
+
+    pub fn set_resizable(&mut self, resizable: bool) {
+        self.commands.push(WindowCommand::Resizable(resizable));
+    }
+
+    pub fn set_fullscreen(&mut self, fullscreen: bool) {
+        self.commands.push(WindowCommand::Fullscreen(fullscreen));
+    }
+
+    pub fn set_maximized(&mut self, maximized: bool) {
+        self.commands.push(WindowCommand::Maximized(maximized));
+    }
+
+    pub fn set_minimized(&mut self, minimized: bool) {
+        self.commands.push(WindowCommand::Minimized(minimized));
+    }
+
+    pub fn set_always_on_top(&mut self, always_on_top: bool) {
+        self.commands
+            .push(WindowCommand::AlwaysOnTop(always_on_top));
+    }
+
+    /// Dumps the view tree with every node's layout once the next frame is laid out,
+    /// the dump is then returned by `tree_dump`.
+    pub fn dump_tree(&mut self, options: DumpOptions) {
+        self.dump = Some(DumpRequest {
+            options,
+            print: false,
+        });
+    }
+
+    /// Like `dump_tree`, but also prints the dump.
+    pub fn print_tree(&mut self, options: DumpOptions) {
+        self.dump = Some(DumpRequest {
+            options,
+            print: true,
+        });
+    }
+
+    /// The latest dump requested with `dump_tree` or `print_tree`.
+    pub fn tree_dump(&self) -> Option<&str> {
+        self.tree_dump.as_deref()
+    }
+
+    /// Requests closing the window, which close request listeners can still cancel.
+    pub fn close(&mut self) {
+        self.close = true;
+    }
+}
+
+mod tests {
+    use super::*;
+    use crate::core::context::tests::TestStates;
+
+    #[test]
+    fn commands_queued_until_taken() {
+        let mut test = TestStates::new();
+        let mut context = test.context_mut();
+        context.window().set_title("Notes");
+        context.window().set_min_size(Some(Vec2::new(200.0, 100.0)));
+        context.window().close();
+
+        let commands = test.window.take_commands();
+        assert!(matches!(
+            &commands[..],
+            [WindowCommand::Title(title), WindowCommand::MinSize(Some(min_size))]
+                if title == "Notes" && *min_size == Vec2::new(200.0, 100.0)
+        ));
+        assert!(test.window.take_commands().is_empty());
+
+        assert!(test.window.take_close());
+        assert!(!test.window.take_close());
+    }
+}
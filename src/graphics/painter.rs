@@ -1,7 +1,11 @@
 use super::{color::Color, paint::Paint, text::Text};
 use crate::math::Vec2;
 use skia_safe::{Canvas, Path, Point, Rect, Vector};
-use std::f32::consts::PI;
+use std::{
+    f32::consts::PI,
+    panic::{self, AssertUnwindSafe},
+    thread,
+};
 
 pub struct Painter<'a> {
     canvas: &'a Canvas,
@@ -24,6 +28,17 @@ impl Painter<'_> {
         }
     }
 
+    // restores the canvas state a panicking `f` may have left saved
+    pub(crate) fn catch_unwind<R>(
+        &mut self,
+        f: impl FnOnce(&mut Painter) -> R,
+    ) -> thread::Result<R> {
+        let save_count = self.canvas.save_count();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| f(self)));
+        self.canvas.restore_to_count(save_count);
+        result
+    }
+
     pub fn draw_rect(&mut self, position: Vec2, size: Vec2, paint: impl Into<Paint>) {
         let paint = paint.into().into();
         if let Some(paint) = paint {
@@ -0,0 +1,88 @@
+use crate::{core::window::ColorScheme, graphics::painter::Painter};
+use skia_safe::{surfaces, AlphaType, Color4f, ColorType, ImageInfo};
+use softbuffer::{Context, Surface};
+use std::{num::NonZeroU32, rc::Rc};
+use winit::window::Window;
+
+// Draws with skia on the CPU and presents the pixels through softbuffer,
+// which works on every platform winit supports without setting up a GPU context.
+pub(crate) struct Renderer {
+    surface: Surface<Rc<Window>, Rc<Window>>,
+    width: u32,
+    height: u32,
+    scale_factor: f64,
+    background: Color4f,
+}
+
+impl Renderer {
+    pub(crate) fn new(window: &Rc<Window>) -> Self {
+        let context = Context::new(window.clone()).expect("Failed to create a softbuffer context");
+        let surface =
+            Surface::new(&context, window.clone()).expect("Failed to create a softbuffer surface");
+
+        let size = window.inner_size();
+        let mut renderer = Self {
+            surface,
+            width: 0,
+            height: 0,
+            scale_factor: window.scale_factor(),
+            background: Color4f::new(1.0, 1.0, 1.0, 1.0),
+        };
+        renderer.set_size(size.width, size.height);
+        renderer
+    }
+
+    pub(crate) fn set_size(&mut self, width: u32, height: u32) {
+        let (Some(buffer_width), Some(buffer_height)) =
+            (NonZeroU32::new(width), NonZeroU32::new(height))
+        else {
+            return;
+        };
+        self.surface
+            .resize(buffer_width, buffer_height)
+            .expect("Failed to resize the softbuffer surface");
+        self.width = width;
+        self.height = height;
+    }
+
+    pub(crate) fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.scale_factor = scale_factor;
+    }
+
+    pub(crate) fn set_color_scheme(&mut self, color_scheme: ColorScheme) {
+        self.background = match color_scheme {
+            ColorScheme::Light => Color4f::new(1.0, 1.0, 1.0, 1.0),
+            ColorScheme::Dark => Color4f::new(0.12, 0.12, 0.12, 1.0),
+        };
+    }
+
+    pub(crate) fn render(&mut self, f: impl FnOnce(&mut Painter)) {
+        let dimensions = (self.width as i32, self.height as i32);
+        let Some(mut surface) = surfaces::raster_n32_premul(dimensions) else {
+            return;
+        };
+
+        // softbuffer has no alpha channel, so the window is cleared to an opaque background that
+        // translucent colors blend over like they would over the window on macOS
+        let canvas = surface.canvas();
+        canvas.clear(self.background);
+        canvas.scale((self.scale_factor as f32, self.scale_factor as f32));
+        f(&mut Painter::new(canvas));
+
+        let row_bytes = self.width as usize * 4;
+        let mut pixels = vec![0; row_bytes * self.height as usize];
+        let info = ImageInfo::new(dimensions, ColorType::BGRA8888, AlphaType::Premul, None);
+        if !surface.read_pixels(&info, &mut pixels, row_bytes, (0, 0)) {
+            return;
+        }
+
+        let Ok(mut buffer) = self.surface.buffer_mut() else {
+            return;
+        };
+        // every pixel is opaque after the clear, softbuffer wants the unused top byte zeroed
+        for (target, pixel) in buffer.iter_mut().zip(pixels.chunks_exact(4)) {
+            *target = u32::from_le_bytes([pixel[0], pixel[1], pixel[2], 0]);
+        }
+        let _ = buffer.present();
+    }
+}
@@ -1,25 +1,93 @@
-use std::ops::{Index, IndexMut};
+use std::{fmt, ops::Index};
 
 pub struct IdVec<T> {
-    nodes: Vec<Option<T>>,
+    slots: Vec<Slot<T>>,
+    free: Vec<usize>,
 }
 
+struct Slot<T> {
+    generation: u32,
+    value: Option<T>,
+}
+
+// The generation tells apart ids of values that were removed from the ones reusing their slot
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
-pub struct Id(pub usize);
+pub struct Id {
+    index: usize,
+    generation: u32,
+}
+
+impl Id {
+    pub(crate) fn new(index: usize, generation: u32) -> Self {
+        Self { index, generation }
+    }
+}
+
+impl fmt::Display for Id {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}v{}", self.index, self.generation)
+    }
+}
+
+impl<T> Default for IdVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl<T> IdVec<T> {
     pub fn new() -> Self {
-        IdVec { nodes: vec![] }
+        IdVec {
+            slots: vec![],
+            free: vec![],
+        }
     }
 
     pub fn insert(&mut self, value: T) -> Id {
-        let id = Id(self.nodes.len());
-        self.nodes.push(Some(value));
-        id
+        if let Some(index) = self.free.pop() {
+            let slot = &mut self.slots[index];
+            slot.value = Some(value);
+            Id::new(index, slot.generation)
+        } else {
+            self.slots.push(Slot {
+                generation: 0,
+                value: Some(value),
+            });
+            Id::new(self.slots.len() - 1, 0)
+        }
     }
 
-    pub fn remove(&mut self, id: Id) {
-        self.nodes[id.0] = None;
+    pub fn remove(&mut self, id: Id) -> Option<T> {
+        let slot = self
+            .slots
+            .get_mut(id.index)
+            .filter(|slot| slot.generation == id.generation)?;
+        let value = slot.value.take()?;
+        // a slot out of generations is retired, reusing it could bring back the ids of old values
+        if let Some(generation) = slot.generation.checked_add(1) {
+            slot.generation = generation;
+            self.free.push(id.index);
+        }
+        Some(value)
+    }
+
+    pub fn contains(&self, id: Id) -> bool {
+        self.try_get(id).is_some()
+    }
+
+    pub fn try_get(&self, id: Id) -> Option<&T> {
+        self.slots
+            .get(id.index)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.value.as_ref())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Id, &T)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            slot.value
+                .as_ref()
+                .map(|value| (Id::new(index, slot.generation), value))
+        })
     }
 }
 
@@ -27,20 +95,40 @@ impl<T> Index<Id> for IdVec<T> {
     type Output = T;
 
     fn index(&self, id: Id) -> &Self::Output {
-        if let Some(node) = &self.nodes[id.0] {
-            node
+        if let Some(value) = self.try_get(id) {
+            value
         } else {
-            panic!("Node not found: {}", id.0);
+            panic!("Node not found: {:?}", id);
         }
     }
 }
 
-impl<T> IndexMut<Id> for IdVec<T> {
-    fn index_mut(&mut self, id: Id) -> &mut Self::Output {
-        if let Some(node) = &mut self.nodes[id.0] {
-            node
-        } else {
-            panic!("Node not found: {}", id.0);
-        }
+mod tests {
+    #[allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    fn reuses_slots() {
+        let mut ids = IdVec::new();
+        let first = ids.insert("first");
+        assert_eq!(ids.remove(first), Some("first"));
+
+        let second = ids.insert("second");
+        assert_eq!(second.index, first.index);
+        assert!(!ids.contains(first));
+        assert_eq!(ids.try_get(first), None);
+        assert_eq!(ids.remove(first), None);
+        assert_eq!(ids[second], "second");
+    }
+
+    #[test]
+    fn retires_exhausted_slots() {
+        let mut ids = IdVec::new();
+        let first = ids.insert("first");
+        ids.slots[first.index].generation = u32::MAX;
+        ids.remove(Id::new(first.index, u32::MAX));
+
+        let second = ids.insert("second");
+        assert_ne!(second.index, first.index);
     }
 }
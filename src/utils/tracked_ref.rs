@@ -9,7 +9,10 @@ pub trait RefTracker {
     fn accessed(&mut self);
 }
 
-pub trait RefMutTracker: RefTracker {
+pub trait RefMutTracker<V>: RefTracker {
+    /// Called with the value before it's first accessed mutably.
+    fn mutating(&mut self, _value: &V) {}
+
     fn accessed_mut(&mut self);
 }
 
@@ -46,14 +49,14 @@ impl<V, T: RefTracker> Drop for TrackedRef<'_, V, T> {
     }
 }
 
-pub struct TrackedRefMut<'a, V, T: RefMutTracker> {
+pub struct TrackedRefMut<'a, V, T: RefMutTracker<V>> {
     value: &'a mut V,
     tracker: T,
     accessed: Cell<bool>,
     accessed_mut: bool,
 }
 
-impl<'a, V, T: RefMutTracker> TrackedRefMut<'a, V, T> {
+impl<'a, V, T: RefMutTracker<V>> TrackedRefMut<'a, V, T> {
     pub fn new(value: &'a mut V, tracker: T) -> Self {
         Self {
             value,
@@ -64,7 +67,7 @@ impl<'a, V, T: RefMutTracker> TrackedRefMut<'a, V, T> {
     }
 }
 
-impl<V, T: RefMutTracker> Deref for TrackedRefMut<'_, V, T> {
+impl<V, T: RefMutTracker<V>> Deref for TrackedRefMut<'_, V, T> {
     type Target = V;
 
     fn deref(&self) -> &Self::Target {
@@ -73,14 +76,17 @@ impl<V, T: RefMutTracker> Deref for TrackedRefMut<'_, V, T> {
     }
 }
 
-impl<V, T: RefMutTracker> DerefMut for TrackedRefMut<'_, V, T> {
+impl<V, T: RefMutTracker<V>> DerefMut for TrackedRefMut<'_, V, T> {
     fn deref_mut(&mut self) -> &mut Self::Target {
+        if !self.accessed_mut {
+            self.tracker.mutating(self.value);
+        }
         self.accessed_mut = true;
         &mut self.value
     }
 }
 
-impl<V, T: RefMutTracker> Drop for TrackedRefMut<'_, V, T> {
+impl<V, T: RefMutTracker<V>> Drop for TrackedRefMut<'_, V, T> {
     fn drop(&mut self) {
         if self.accessed.get() {
             self.tracker.accessed();
@@ -106,7 +112,7 @@ mod tests {
         }
     }
 
-    impl RefMutTracker for (&mut bool, &mut bool) {
+    impl<V> RefMutTracker<V> for (&mut bool, &mut bool) {
         fn accessed_mut(&mut self) {
             *self.1 = true;
         }
@@ -0,0 +1,74 @@
+use super::ViewBuilder;
+use crate::math::Vec2;
+use crate::{
+    core::{
+        animation::Curve,
+        constraints::Constraints,
+        context::{Context, ContextMut},
+        interaction::Interaction,
+        layout::Layout,
+        view::View,
+        view_tree::{ViewDrawer, ViewInteractor, ViewSizer},
+    },
+    graphics::painter::Painter,
+};
+use std::rc::Rc;
+
+#[derive(PartialEq)]
+pub struct AnimatedLayout {
+    curve: Curve,
+    view: ViewBuilder,
+}
+
+pub trait LayoutAnimatable: View + Sized {
+    fn animate_layout(self, curve: Curve) -> AnimatedLayout {
+        AnimatedLayout {
+            curve,
+            view: ViewBuilder::from_view(self),
+        }
+    }
+}
+
+impl<V: View + Sized> LayoutAnimatable for V {}
+
+impl View for AnimatedLayout {
+    fn build(&self, context: &mut Context) -> Vec<Rc<dyn View>> {
+        vec![self.view.build()]
+    }
+
+    fn size(&self, constraints: Constraints, children: &[ViewSizer]) -> Vec2 {
+        children[0].size(constraints)
+    }
+
+    fn layout(&self, layout: Layout, children: &[ViewSizer]) -> Vec<Layout> {
+        vec![Layout {
+            position: Vec2::ZERO,
+            size: layout.size,
+        }]
+    }
+
+    fn draw(&self, layout: Layout, painter: &mut Painter, children: &[ViewDrawer]) {
+        painter.translate(layout.position, |painter| {
+            children[0].draw(painter);
+        });
+    }
+
+    fn interact(
+        &self,
+        context: &mut ContextMut,
+        layout: Layout,
+        interaction: Interaction,
+        consumed: bool,
+        children: &[ViewInteractor],
+    ) -> bool {
+        children[0].interact(
+            context,
+            interaction.translate_into(layout.position),
+            consumed,
+        )
+    }
+
+    fn layout_curve(&self) -> Option<Curve> {
+        Some(self.curve)
+    }
+}
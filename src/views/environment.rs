@@ -0,0 +1,70 @@
+use super::ViewBuilder;
+use crate::math::Vec2;
+use crate::{
+    core::{
+        constraints::Constraints,
+        context::{Context, ContextMut},
+        interaction::Interaction,
+        layout::Layout,
+        view::View,
+        view_tree::{ViewDrawer, ViewInteractor, ViewSizer},
+    },
+    graphics::painter::Painter,
+};
+use std::rc::Rc;
+
+#[derive(PartialEq)]
+pub struct Environment<T: Clone + PartialEq + 'static> {
+    value: T,
+    view: ViewBuilder,
+}
+
+pub trait EnvironmentExt: View + Sized {
+    fn environment<T: Clone + PartialEq + 'static>(self, value: T) -> Environment<T> {
+        Environment {
+            value,
+            view: ViewBuilder::from_view(self),
+        }
+    }
+}
+
+impl<V: View + Sized> EnvironmentExt for V {}
+
+impl<T: Clone + PartialEq + 'static> View for Environment<T> {
+    fn build(&self, context: &mut Context) -> Vec<Rc<dyn View>> {
+        context.provide(self.value.clone());
+        vec![self.view.build()]
+    }
+
+    fn size(&self, constraints: Constraints, children: &[ViewSizer]) -> Vec2 {
+        children[0].size(constraints)
+    }
+
+    fn layout(&self, layout: Layout, children: &[ViewSizer]) -> Vec<Layout> {
+        vec![Layout {
+            position: Vec2::ZERO,
+            size: layout.size,
+        }]
+    }
+
+    fn draw(&self, layout: Layout, painter: &mut Painter, children: &[ViewDrawer]) {
+        painter.translate(layout.position, |painter| {
+            children[0].draw(painter);
+        });
+    }
+
+    fn interact(
+        &self,
+        context: &mut ContextMut,
+        layout: Layout,
+        interaction: Interaction,
+        consumed: bool,
+        children: &[ViewInteractor],
+    ) -> bool {
+        children[0].interact(
+            context,
+            interaction.translate_into(layout.position),
+            consumed,
+        )
+    }
+}
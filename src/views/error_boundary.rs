@@ -0,0 +1,100 @@
+use super::ViewBuilder;
+use crate::math::Vec2;
+use crate::{
+    core::{
+        constraints::Constraints,
+        context::{Context, ContextMut},
+        error::ViewError,
+        interaction::Interaction,
+        layout::Layout,
+        view::View,
+        view_tree::{ViewDrawer, ViewInteractor, ViewSizer},
+    },
+    graphics::painter::Painter,
+    utils::id_vec::Id,
+};
+use std::rc::Rc;
+
+type Fallback = Rc<dyn Fn(&ViewError, Retry) -> Rc<dyn View> + 'static>;
+
+pub struct ErrorBoundary {
+    fallback: Fallback,
+    view: ViewBuilder,
+}
+
+/// Given to the fallback of an error boundary to build the failed subtree again.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Retry(Id);
+
+impl Retry {
+    pub fn retry(self, context: &mut ContextMut) {
+        context.clear_boundary(self.0);
+    }
+}
+
+impl PartialEq for ErrorBoundary {
+    fn eq(&self, other: &Self) -> bool {
+        self.view == other.view && Rc::ptr_eq(&self.fallback, &other.fallback)
+    }
+}
+
+pub trait ErrorBoundaryExt: View + Sized {
+    /// Errors and panics from the build, layout or draw of this subtree
+    /// replace it with the fallback.
+    fn error_boundary<V: View, F: Fn(&ViewError, Retry) -> V + 'static>(
+        self,
+        fallback: F,
+    ) -> ErrorBoundary {
+        ErrorBoundary {
+            fallback: Rc::new(move |error, retry| Rc::new(fallback(error, retry))),
+            view: ViewBuilder::from_view(self),
+        }
+    }
+}
+
+impl<V: View + Sized> ErrorBoundaryExt for V {}
+
+impl View for ErrorBoundary {
+    fn build(&self, context: &mut Context) -> Vec<Rc<dyn View>> {
+        match context.boundary_error() {
+            Some(error) => vec![(self.fallback)(&error, Retry(context.id().unwrap()))],
+            None => vec![self.view.build()],
+        }
+    }
+
+    fn size(&self, constraints: Constraints, children: &[ViewSizer]) -> Vec2 {
+        children[0].size(constraints)
+    }
+
+    fn layout(&self, layout: Layout, children: &[ViewSizer]) -> Vec<Layout> {
+        vec![Layout {
+            position: Vec2::ZERO,
+            size: layout.size,
+        }]
+    }
+
+    fn draw(&self, layout: Layout, painter: &mut Painter, children: &[ViewDrawer]) {
+        painter.translate(layout.position, |painter| {
+            children[0].draw(painter);
+        });
+    }
+
+    fn interact(
+        &self,
+        context: &mut ContextMut,
+        layout: Layout,
+        interaction: Interaction,
+        consumed: bool,
+        children: &[ViewInteractor],
+    ) -> bool {
+        children[0].interact(
+            context,
+            interaction.translate_into(layout.position),
+            consumed,
+        )
+    }
+
+    fn is_error_boundary(&self) -> bool {
+        true
+    }
+}
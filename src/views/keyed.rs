@@ -0,0 +1,78 @@
+use super::ViewBuilder;
+use crate::math::Vec2;
+use crate::{
+    core::{
+        constraints::Constraints,
+        context::{Context, ContextMut},
+        interaction::Interaction,
+        layout::Layout,
+        view::View,
+        view_tree::{ViewDrawer, ViewInteractor, ViewSizer},
+    },
+    graphics::painter::Painter,
+};
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    rc::Rc,
+};
+
+#[derive(PartialEq)]
+pub struct Keyed {
+    key: u64,
+    view: ViewBuilder,
+}
+
+pub trait Keyable: View + Sized {
+    fn keyed(self, key: impl Hash) -> Keyed {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        Keyed {
+            key: hasher.finish(),
+            view: ViewBuilder::from_view(self),
+        }
+    }
+}
+
+impl<V: View + Sized> Keyable for V {}
+
+impl View for Keyed {
+    fn build(&self, context: &mut Context) -> Vec<Rc<dyn View>> {
+        vec![self.view.build()]
+    }
+
+    fn size(&self, constraints: Constraints, children: &[ViewSizer]) -> Vec2 {
+        children[0].size(constraints)
+    }
+
+    fn layout(&self, layout: Layout, children: &[ViewSizer]) -> Vec<Layout> {
+        vec![Layout {
+            position: Vec2::ZERO,
+            size: layout.size,
+        }]
+    }
+
+    fn draw(&self, layout: Layout, painter: &mut Painter, children: &[ViewDrawer]) {
+        painter.translate(layout.position, |painter| {
+            children[0].draw(painter);
+        });
+    }
+
+    fn interact(
+        &self,
+        context: &mut ContextMut,
+        layout: Layout,
+        interaction: Interaction,
+        consumed: bool,
+        children: &[ViewInteractor],
+    ) -> bool {
+        children[0].interact(
+            context,
+            interaction.translate_into(layout.position),
+            consumed,
+        )
+    }
+
+    fn key(&self) -> Option<u64> {
+        Some(self.key)
+    }
+}
@@ -0,0 +1,117 @@
+use super::ViewBuilder;
+use crate::math::Vec2;
+use crate::{
+    core::{
+        constraints::Constraints,
+        context::{Context, ContextMut},
+        interaction::{Interaction, Lifecycle},
+        layout::Layout,
+        view::View,
+        view_tree::{ViewDrawer, ViewInteractor, ViewSizer},
+    },
+    graphics::painter::Painter,
+};
+use std::rc::Rc;
+
+type LifecycleAction = Rc<dyn Fn(&mut ContextMut, Lifecycle) -> bool + 'static>;
+
+pub struct LifecycleListener {
+    action: LifecycleAction,
+    view: ViewBuilder,
+}
+
+impl PartialEq for LifecycleListener {
+    fn eq(&self, other: &Self) -> bool {
+        self.view == other.view && Rc::ptr_eq(&self.action, &other.action)
+    }
+}
+
+pub trait LifecycleListenerExt: View + Sized {
+    /// The action returns true to consume the event, which for
+    /// `Lifecycle::CloseRequested` keeps the window open.
+    fn on_lifecycle<A: Fn(&mut ContextMut, Lifecycle) -> bool + 'static>(
+        self,
+        action: A,
+    ) -> LifecycleListener {
+        LifecycleListener {
+            action: Rc::new(action),
+            view: ViewBuilder::from_view(self),
+        }
+    }
+
+    /// The action returns true to cancel closing the window.
+    fn on_close_requested<A: Fn(&mut ContextMut) -> bool + 'static>(
+        self,
+        action: A,
+    ) -> LifecycleListener {
+        self.on_lifecycle(move |ctx, lifecycle| {
+            lifecycle == Lifecycle::CloseRequested && action(ctx)
+        })
+    }
+
+    fn on_focus_changed<A: Fn(&mut ContextMut, bool) + 'static>(
+        self,
+        action: A,
+    ) -> LifecycleListener {
+        self.on_lifecycle(move |ctx, lifecycle| {
+            if let Lifecycle::Focused(focused) = lifecycle {
+                action(ctx, focused);
+            }
+            false
+        })
+    }
+
+    fn on_exit<A: Fn(&mut ContextMut) + 'static>(self, action: A) -> LifecycleListener {
+        self.on_lifecycle(move |ctx, lifecycle| {
+            if lifecycle == Lifecycle::Exiting {
+                action(ctx);
+            }
+            false
+        })
+    }
+}
+
+impl<V: View + Sized> LifecycleListenerExt for V {}
+
+impl View for LifecycleListener {
+    fn build(&self, context: &mut Context) -> Vec<Rc<dyn View>> {
+        vec![self.view.build()]
+    }
+
+    fn size(&self, constraints: Constraints, children: &[ViewSizer]) -> Vec2 {
+        children[0].size(constraints)
+    }
+
+    fn layout(&self, layout: Layout, children: &[ViewSizer]) -> Vec<Layout> {
+        vec![Layout {
+            position: Vec2::ZERO,
+            size: layout.size,
+        }]
+    }
+
+    fn draw(&self, layout: Layout, painter: &mut Painter, children: &[ViewDrawer]) {
+        painter.translate(layout.position, |painter| {
+            children[0].draw(painter);
+        });
+    }
+
+    fn interact(
+        &self,
+        context: &mut ContextMut,
+        layout: Layout,
+        interaction: Interaction,
+        consumed: bool,
+        children: &[ViewInteractor],
+    ) -> bool {
+        let child_consumed = children[0].interact(
+            context,
+            interaction.translate_into(layout.position),
+            consumed,
+        );
+        if let Interaction::Lifecycle(lifecycle) = interaction {
+            (self.action)(context, lifecycle) || child_consumed
+        } else {
+            child_consumed
+        }
+    }
+}
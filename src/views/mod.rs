@@ -1,18 +1,28 @@
 #![allow(unused)]
+pub mod animated_layout;
 pub mod component;
 pub mod decoration;
+pub mod environment;
+pub mod error_boundary;
 pub mod flex;
+pub mod keyed;
 pub mod label;
+pub mod lifecycle;
 pub mod mouse;
 pub mod padding;
 pub mod spacer;
 
 pub mod prelude {
     pub use super::{
+        animated_layout::LayoutAnimatable,
         component::Component,
         decoration::{BorderDecoration, BoxDecoration, Decoratable, Decoration},
+        environment::EnvironmentExt,
+        error_boundary::{ErrorBoundaryExt, Retry},
         flex::{col, row},
+        keyed::Keyable,
         label::label,
+        lifecycle::LifecycleListenerExt,
         mouse::{MouseListenerExt, MouseState},
         padding::Paddable,
         spacer::spacer,
@@ -22,7 +32,8 @@ pub mod prelude {
 }
 
 use crate::core::view::View;
-use std::rc::Rc;
+use keyed::Keyable;
+use std::{hash::Hash, rc::Rc};
 
 pub struct ViewBuilder {
     builder: Rc<dyn Fn() -> Rc<dyn View>>,
@@ -87,6 +98,28 @@ impl ContentBuilder {
         )
     }
 
+    pub fn from_keyed_items<
+        T,
+        K: Hash,
+        V: View,
+        I: Iterator<Item = T>,
+        KF: FnMut(&T) -> K,
+        F: FnMut(T) -> V,
+    >(
+        items: I,
+        mut key: KF,
+        mut builder: F,
+    ) -> Self {
+        Self::from_vec(
+            items
+                .map::<Rc<dyn View>, _>(|item| {
+                    let key = key(&item);
+                    Rc::new(builder(item).keyed(key))
+                })
+                .collect(),
+        )
+    }
+
     pub fn build(&self) -> Vec<Rc<dyn View>> {
         (self.builder)()
     }